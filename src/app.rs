@@ -1,54 +1,421 @@
+use cursive::traits::*;
 use cursive::views::{Dialog, LinearLayout, TextView};
 use cursive::Cursive;
-use std::error::Error;
-
-use AVALANCHE::UserData;
+use AVALANCHE::config::Config;
+use AVALANCHE::history::History;
+use AVALANCHE::journal::Journal;
+use AVALANCHE::lock::{self, DataLock, FileState};
+use AVALANCHE::pomodoro::Pomodoro;
+use AVALANCHE::{backups, AvalancheError, Edit, Habit, UserData};
 
 pub mod habits_page;
+pub mod import_export;
+pub mod pomodoro;
+pub mod profiles;
 pub mod records_page;
+pub mod recovery;
+pub mod restore;
+pub mod timer;
 
 pub struct AppData {
     pub data_file_name: String,
     pub user_data: UserData,
-    pub selected_habit: Option<usize>,
+    // ID of the habit open on the records page
+    pub selected_habit: Option<u64>,
     pub unsaved_changes: bool,
+    // Edits made since the last save, in case the app doesn't exit cleanly
+    pub journal: Journal,
+    pub history: History,
+    // Held while this instance may write to the data file
+    pub lock: Option<DataLock>,
+    // Set when another instance has the data file open
+    pub read_only: bool,
+    // The data file as it was last read or written
+    pub file_state: Option<FileState>,
+    // The focus session, which isn't saved
+    pub pomodoro: Option<Pomodoro>,
+    pub config: Config,
+    // Name of the profile the data file belongs to, if it's one of them
+    pub profile: Option<String>,
 }
 
-fn quit(s: &mut Cursive) {
+impl AppData {
+    // Only valid while the records page is open
+    pub fn current_habit(&self) -> &Habit {
+        self.user_data
+            .find_habit(self.selected_habit.unwrap())
+            .unwrap()
+    }
+}
+
+// Loads the data file and shows the habits page, or the recovery screen if
+// the file can't be read
+pub fn open_data_file(s: &mut Cursive) {
     let app_data = s.user_data::<AppData>().unwrap();
-    if app_data.unsaved_changes {
-        s.add_layer(
-            Dialog::around(
-                LinearLayout::vertical()
-                    .child(TextView::new("There are unsaved changes."))
-                    .child(TextView::new("Are you sure you want to quit?")),
-            )
-            .button("Save and quit", |s| {
-                if save_data(s).is_ok() {
-                    s.quit();
+    let filename = app_data.data_file_name.clone();
+    match UserData::try_from_file(&filename) {
+        Ok(user_data) => {
+            app_data.user_data = user_data;
+            app_data.file_state = FileState::of(&filename).unwrap_or(None);
+            let read_only = app_data.read_only;
+            habits_page::draw(s);
+            // The journal belongs to the instance holding the lock
+            if !read_only {
+                offer_journal_replay(s);
+            }
+        }
+        Err(error) => recovery::draw(s, &error),
+    }
+}
+
+// Shown instead of opening the data file when the lock can't be taken
+pub fn lock_warning(s: &mut Cursive, error: &AvalancheError) {
+    s.add_layer(
+        Dialog::text(format!(
+            "{}\n\nIt can be opened read-only, or anyway if that instance is gone.",
+            error
+        ))
+        .title("Data file in use")
+        .button("Open read-only", |s| {
+            s.user_data::<AppData>().unwrap().read_only = true;
+            s.pop_layer();
+            open_data_file(s);
+        })
+        .button("Open anyway", |s| {
+            let app_data = s.user_data::<AppData>().unwrap();
+            match DataLock::take_over(&app_data.data_file_name) {
+                Ok(lock) => {
+                    app_data.lock = Some(lock);
+                    s.pop_layer();
+                    open_data_file(s);
                 }
-            })
-            .button("Quit", Cursive::quit)
-            .button("Cancel", |s| {
+                Err(error) => {
+                    s.add_layer(Dialog::info(format!("Failed to take the lock:\n{}", error)))
+                }
+            }
+        })
+        .button("Quit", Cursive::quit),
+    );
+}
+
+fn quit(s: &mut Cursive) {
+    leave_data_file(s, "quit", Cursive::quit);
+}
+
+/* Offers to save any unsaved changes, then calls then to close the data
+ * file. label, like "quit", goes on the buttons.
+ */
+pub fn leave_data_file<F>(s: &mut Cursive, label: &str, then: F)
+where
+    F: 'static + Fn(&mut Cursive) + Send + Sync + Clone,
+{
+    let app_data = s.user_data::<AppData>().unwrap();
+    if !app_data.unsaved_changes {
+        then(s);
+        return;
+    }
+    let discard = then.clone();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("There are unsaved changes."))
+                .child(TextView::new(format!(
+                    "Are you sure you want to {}?",
+                    label
+                ))),
+        )
+        .button(format!("Save and {}", label), move |s| {
+            if save_data(s).is_ok() {
                 s.pop_layer();
-            }),
-        );
-    } else {
-        s.quit();
+                then(s);
+            }
+        })
+        .button(capitalized(label), move |s| {
+            // Changes are thrown away on purpose, so they shouldn't be
+            // offered again on the next launch
+            let app_data = s.user_data::<AppData>().unwrap();
+            app_data.journal.clear().unwrap_or(());
+            app_data.unsaved_changes = false;
+            s.pop_layer();
+            discard(s);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+pub fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
     }
 }
 
-fn save_data(s: &mut Cursive) -> Result<(), Box<dyn Error>> {
+/* Closes the open data file and opens filename in its place, as if the app
+ * had been started with it. Anything unsaved should be dealt with first.
+ */
+pub fn switch_data_file(s: &mut Cursive, filename: String, profile: Option<String>) {
+    while s.pop_layer().is_some() {}
+    s.menubar().clear();
     let app_data = s.user_data::<AppData>().unwrap();
+    // Let go of the old lock first, in case it's the same file
+    app_data.lock = None;
+    app_data.journal = Journal::for_data_file(&filename);
+    app_data.data_file_name = filename.clone();
+    app_data.profile = profile;
+    app_data.user_data = UserData::default();
+    app_data.selected_habit = None;
+    app_data.unsaved_changes = false;
+    app_data.history = History::default();
+    app_data.read_only = false;
+    app_data.file_state = None;
+    app_data.pomodoro = None;
+    match DataLock::acquire(&filename) {
+        Ok(lock) => {
+            s.user_data::<AppData>().unwrap().lock = Some(lock);
+            open_data_file(s);
+        }
+        Err(error) => lock_warning(s, &error),
+    }
+}
+
+fn save_data(s: &mut Cursive) -> Result<(), AvalancheError> {
+    let app_data = s.user_data::<AppData>().unwrap();
+    if app_data.read_only {
+        s.add_layer(Dialog::info("Opened read-only, so changes can't be saved"));
+        return Err(AvalancheError::ReadOnly);
+    }
+    if let Err(error) = lock::check_unchanged(&app_data.data_file_name, &app_data.file_state) {
+        match error {
+            AvalancheError::ChangedOnDisk => file_changed(s),
+            _ => s.add_layer(Dialog::info(format!(
+                "Failed to check the data file, nothing was saved:\n{}",
+                error
+            ))),
+        }
+        return Err(error);
+    }
+    write_data(s)
+}
+
+// Saves over whatever is in the data file
+fn write_data(s: &mut Cursive) -> Result<(), AvalancheError> {
+    let app_data = s.user_data::<AppData>().unwrap();
+    if let Err(error) = backups::rotate(&app_data.data_file_name, app_data.config.backup_count) {
+        s.add_layer(Dialog::info(format!(
+            "Failed to back up the data file, nothing was saved:\n{}",
+            error
+        )));
+        return Err(error);
+    }
     let user_data = &mut app_data.user_data;
     match user_data.write_to_file(&app_data.data_file_name) {
         Ok(_) => {
             app_data.unsaved_changes = false;
-            return Ok(());
+            app_data.journal.clear().unwrap_or(());
+            app_data.file_state = FileState::of(&app_data.data_file_name).unwrap_or(None);
+            Ok(())
+        }
+        Err(error) => {
+            s.add_layer(Dialog::info(format!(
+                "Failed to write to data file:\n{}",
+                error
+            )));
+            Err(error)
+        }
+    }
+}
+
+// Makes a change to the user data and writes it to the journal. Returns
+// false if the change couldn't be made.
+pub fn apply_edit(s: &mut Cursive, edit: Edit) -> bool {
+    let app_data = s.user_data::<AppData>().unwrap();
+    if app_data.read_only {
+        s.add_layer(Dialog::info("Opened read-only, so nothing can be changed"));
+        return false;
+    }
+    if !app_data
+        .history
+        .apply(edit.clone(), &mut app_data.user_data)
+    {
+        return false;
+    }
+    edit_applied(s, &edit);
+    true
+}
+
+fn edit_applied(s: &mut Cursive, edit: &Edit) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    app_data.unsaved_changes = true;
+    if let Err(error) = app_data.journal.append(edit) {
+        s.add_layer(Dialog::info(format!(
+            "Failed to write to the recovery journal:\n{}",
+            error
+        )));
+    }
+}
+
+pub fn undo(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    match app_data.history.undo(&mut app_data.user_data) {
+        Some(edit) => {
+            edit_applied(s, &edit);
+            redraw_pages(s);
+        }
+        None => s.add_layer(Dialog::info("Nothing to undo")),
+    }
+}
+
+pub fn redo(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    match app_data.history.redo(&mut app_data.user_data) {
+        Some(edit) => {
+            edit_applied(s, &edit);
+            redraw_pages(s);
+        }
+        None => s.add_layer(Dialog::info("Nothing to redo")),
+    }
+}
+
+// Shows the data again after it changed underneath the open pages
+fn redraw_pages(s: &mut Cursive) {
+    habits_page::fill_habit_select(s);
+    if s.user_data::<AppData>().unwrap().selected_habit.is_some() {
+        records_page::redraw(s);
+    }
+}
+
+// Keeps the running timer and pomodoro counting, every second
+pub fn tick(s: &mut Cursive) {
+    timer::tick(s);
+    pomodoro::tick(s);
+}
+
+// Saves without asking, if there's anything to save
+pub fn autosave(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    if app_data.unsaved_changes && !app_data.read_only {
+        save_data(s).unwrap_or(());
+    }
+}
+
+/* Edits left in the journal weren't saved before the last session ended.
+ * They're replayed on top of the data file if the user wants them back.
+ */
+pub fn offer_journal_replay(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let edits = match app_data.journal.read() {
+        Ok(edits) => edits,
+        Err(error) => {
+            s.add_layer(Dialog::info(format!(
+                "Failed to read the recovery journal:\n{}",
+                error
+            )));
+            return;
+        }
+    };
+    if edits.is_empty() {
+        return;
+    }
+
+    s.add_layer(
+        Dialog::text(format!(
+            "The last session ended with {} unsaved changes. Restore them?",
+            edits.len()
+        ))
+        .title("Unsaved changes")
+        .button("Restore", move |s| {
+            let app_data = s.user_data::<AppData>().unwrap();
+            let skipped = app_data.user_data.replay(&edits);
+            app_data.unsaved_changes = true;
+            s.pop_layer();
+            habits_page::fill_habit_select(s);
+            if skipped > 0 {
+                s.add_layer(Dialog::info(format!(
+                    "{} changes no longer applied to the data file and were skipped",
+                    skipped
+                )));
+            }
+        })
+        .button("Discard", |s| {
+            let app_data = s.user_data::<AppData>().unwrap();
+            app_data.journal.clear().unwrap_or(());
+            s.pop_layer();
+        }),
+    );
+}
+
+/* The data file was changed by something else since it was loaded. Merging
+ * replays this session's edits from the journal on top of the file.
+ */
+fn file_changed(s: &mut Cursive) {
+    // Autosave can run into this again while the dialog is still open
+    if s.find_name::<Dialog>("file_changed").is_some() {
+        return;
+    }
+
+    s.add_layer(
+        Dialog::text(format!(
+            "{}.\n\nReload it and lose the changes made here, overwrite it, \
+             or merge the changes made here into it?",
+            AvalancheError::ChangedOnDisk
+        ))
+        .title("Data file changed")
+        .button("Reload", |s| {
+            if let Some(user_data) = read_changed_file(s) {
+                let app_data = s.user_data::<AppData>().unwrap();
+                app_data.user_data = user_data;
+                app_data.unsaved_changes = false;
+                app_data.journal.clear().unwrap_or(());
+                s.pop_layer();
+                redraw_pages(s);
+            }
+        })
+        .button("Overwrite", |s| {
+            s.pop_layer();
+            write_data(s).unwrap_or(());
+        })
+        .button("Merge", |s| {
+            if let Some(mut user_data) = read_changed_file(s) {
+                let app_data = s.user_data::<AppData>().unwrap();
+                let edits = app_data.journal.read().unwrap_or_default();
+                let skipped = user_data.replay(&edits);
+                app_data.user_data = user_data;
+                s.pop_layer();
+                redraw_pages(s);
+                if write_data(s).is_ok() && skipped > 0 {
+                    s.add_layer(Dialog::info(format!(
+                        "{} changes conflicted with the file and were skipped",
+                        skipped
+                    )));
+                }
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        })
+        .with_name("file_changed"),
+    );
+}
+
+// Reads the data file again. The undo history doesn't apply to it.
+fn read_changed_file(s: &mut Cursive) -> Option<UserData> {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let filename = app_data.data_file_name.clone();
+    match UserData::try_from_file(&filename) {
+        Ok(user_data) => {
+            app_data.history.clear();
+            app_data.file_state = FileState::of(&filename).unwrap_or(None);
+            Some(user_data)
         }
         Err(error) => {
-            s.add_layer(Dialog::info("Failed to write to data file."));
-            return Err(error);
+            s.add_layer(Dialog::info(format!(
+                "Failed to read the data file:\n{}",
+                error
+            )));
+            None
         }
     }
 }
@@ -1,43 +1,83 @@
-use cursive::event::Key;
+use cursive::event::{Event, Key};
 use cursive::traits::*;
-use cursive::views::{Button, Dialog, DummyView, EditView, LinearLayout, SelectView};
-use cursive::{Cursive, CursiveRunnable};
+use cursive::views::{
+    Button, Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView,
+};
+use cursive::Cursive;
 
 use crate::app;
 use crate::app::AppData;
-use crate::Habit;
+use crate::{new_id, Clock, Edit, Habit, HabitKind, Record, SystemClock};
 
-pub fn draw(mut s: CursiveRunnable) {
-    draw_menubar(&mut s);
+pub fn draw(s: &mut Cursive) {
+    draw_menubar(s);
     s.set_autohide_menu(false);
 
-    let app_data = s.user_data::<AppData>().unwrap();
-    let user_data = app_data.user_data.clone();
+    let habit_select = OnEventView::new(
+        SelectView::<u64>::new()
+            .on_submit(app::records_page::draw)
+            .with_name("habit_select"),
+    )
+    .on_event('d', done_today)
+    .scrollable();
 
-    let habit_select = SelectView::<String>::new()
-        .on_submit(app::records_page::draw)
-        .with_name("habit_select")
-        .scrollable();
+    // Says which data file is open, unless it's the default one
+    let app_data = s.user_data::<AppData>().unwrap();
+    let title = match &app_data.profile {
+        Some(profile) => format!("AVALANCHE: {}", profile),
+        None if Some(&app_data.data_file_name) != app_data.config.data_file_path().as_ref() => {
+            format!("AVALANCHE: {}", app_data.data_file_name)
+        }
+        None => String::from("AVALANCHE"),
+    };
 
     s.add_layer(
         Dialog::around(
             LinearLayout::vertical()
-                .child(Dialog::text("Press esc to select the menu"))
+                .child(Dialog::text(
+                    "Press esc to select the menu, d to check in the selected habit for today, \
+                     ctrl-z and ctrl-y to undo and redo",
+                ))
                 .child(habit_select)
                 .full_screen(),
         )
-        .title("AVALANCHE"),
+        .title(title),
     );
 
-    for habit in &user_data.habits {
-        s.call_on_name("habit_select", |view: &mut SelectView<String>| {
-            view.add_item_str(habit.name.as_str());
-        });
-    }
+    fill_habit_select(s);
 
     s.add_global_callback(Key::Esc, |s| s.select_menubar());
+    s.add_global_callback(Event::CtrlChar('z'), app::undo);
+    s.add_global_callback(Event::CtrlChar('y'), app::redo);
+}
 
-    s.run();
+// Lists the habits again, for when the data was replaced
+pub fn fill_habit_select(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let user_data = app_data.user_data.clone();
+    let pomodoro_habit = app_data.pomodoro.as_ref().map(|session| session.habit_id);
+    s.call_on_name("habit_select", |view: &mut SelectView<u64>| {
+        let selected = view.selection().map(|id| *id);
+        view.clear();
+        for habit in &user_data.habits {
+            match &user_data.timer {
+                Some(timer) if timer.habit_id == habit.id => {
+                    view.add_item(format!("{} (timer running)", habit.name), habit.id)
+                }
+                _ if pomodoro_habit == Some(habit.id) => {
+                    view.add_item(format!("{} (pomodoro running)", habit.name), habit.id)
+                }
+                _ => view.add_item(habit.name.as_str(), habit.id),
+            }
+        }
+        if let Some(index) = user_data
+            .habits
+            .iter()
+            .position(|habit| Some(habit.id) == selected)
+        {
+            view.set_selection(index);
+        }
+    });
 }
 
 pub fn draw_menubar(s: &mut Cursive) {
@@ -47,75 +87,208 @@ pub fn draw_menubar(s: &mut Cursive) {
         .add_delimiter()
         .add_leaf("Delete selection", delete_habit)
         .add_delimiter()
+        .add_leaf("Done today (d)", done_today)
+        .add_delimiter()
         .add_leaf("Save", |s| {
             app::save_data(s).unwrap_or(());
         })
         .add_delimiter()
+        .add_leaf("Undo (Ctrl-Z)", app::undo)
+        .add_delimiter()
+        .add_leaf("Redo (Ctrl-Y)", app::redo)
+        .add_delimiter()
+        .add_leaf("Restore backup", app::restore::draw)
+        .add_delimiter()
+        .add_subtree("Import/Export", app::import_export::menu())
+        .add_delimiter()
+        .add_leaf("Switch profile", app::profiles::switch_profile)
+        .add_delimiter()
+        .add_leaf("Open file…", app::profiles::open_file)
+        .add_delimiter()
         .add_leaf("Quit", app::quit);
 }
 
 fn add_habit(s: &mut Cursive) {
     fn ok(s: &mut Cursive, name: &str) {
+        // Spaces around the name are dropped, like on the command line
+        let name = name.trim();
+        if name.is_empty() {
+            s.add_layer(Dialog::info("The name can't be empty"));
+            return;
+        }
         let app_data = s.user_data::<AppData>().unwrap();
         let user_data = &mut app_data.user_data;
         if user_data.find_habit_by_name(name).is_some() {
-            // Name already in use. For now, habits are uniquely identified
-            // by name, so this won't be allowed.
+            // Name already in use. Habits are identified by ID, but names
+            // are still kept unique so they can be told apart in the list.
 
             s.add_layer(Dialog::info("Name already in use"));
             return;
         }
 
-        user_data.habits.push(Habit {
+        let mut kind = s
+            .call_on_name("kind", |view: &mut SelectView<HabitKind>| view.selection())
+            .unwrap()
+            .map(|kind| (*kind).clone())
+            .unwrap_or_default();
+        if let HabitKind::Quantity { unit } = &mut kind {
+            *unit = s
+                .call_on_name("unit", |view: &mut EditView| view.get_content())
+                .unwrap()
+                .trim()
+                .to_string();
+            if unit.is_empty() {
+                s.add_layer(Dialog::info("Enter a unit, like pages or glasses"));
+                return;
+            }
+        }
+
+        let id = new_id();
+        let index = s.user_data::<AppData>().unwrap().user_data.habits.len();
+        let habit = Habit {
+            id,
             name: String::from(name),
             records: Vec::new(),
-        });
-        app_data.unsaved_changes = true;
+            kind,
+            start_date: Some(SystemClock.today()),
+            ..Default::default()
+        };
+        if !app::apply_edit(s, Edit::InsertHabit { index, habit }) {
+            return;
+        }
 
-        s.call_on_name("habit_select", |view: &mut SelectView<String>| {
-            view.add_item_str(name)
+        s.call_on_name("habit_select", |view: &mut SelectView<u64>| {
+            view.add_item(name, id)
         });
 
         s.pop_layer();
     }
 
+    let mut kind_select = SelectView::<HabitKind>::new().popup();
+    kind_select.add_item("Track time spent", HabitKind::Timed);
+    kind_select.add_item("Quit (track relapses)", HabitKind::Quit);
+    kind_select.add_item("Check in (yes/no)", HabitKind::CheckIn);
+    kind_select.add_item(
+        "Measure an amount",
+        HabitKind::Quantity {
+            unit: String::new(),
+        },
+    );
+    // Starts on the kind from the config, with its unit filled in
+    let default_kind = s
+        .user_data::<AppData>()
+        .unwrap()
+        .config
+        .default_habit_kind
+        .clone();
+    let default_unit = match &default_kind {
+        HabitKind::Quantity { unit } => unit.clone(),
+        _ => String::new(),
+    };
+    kind_select.set_selection(match default_kind {
+        HabitKind::Timed => 0,
+        HabitKind::Quit => 1,
+        HabitKind::CheckIn => 2,
+        HabitKind::Quantity { .. } => 3,
+    });
+
     s.add_layer(
-        Dialog::around(EditView::new().on_submit(ok).with_name("name"))
-            .title("Enter a name for the habit")
-            .button("Ok", |s| {
-                let name = s
-                    .call_on_name("name", |view: &mut EditView| view.get_content())
-                    .unwrap();
-                ok(s, &name);
-            })
-            .button("Cancel", |s| {
-                s.pop_layer();
-            }),
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(EditView::new().on_submit(ok).with_name("name"))
+                .child(kind_select.with_name("kind"))
+                .child(
+                    LinearLayout::horizontal()
+                        .child(TextView::new("Unit (for amounts): "))
+                        .child(
+                            EditView::new()
+                                .content(default_unit)
+                                .with_name("unit")
+                                .fixed_width(12),
+                        ),
+                ),
+        )
+        .title("Enter a name for the habit")
+        .button("Ok", |s| {
+            let name = s
+                .call_on_name("name", |view: &mut EditView| view.get_content())
+                .unwrap();
+            ok(s, &name);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
     );
 }
 
+// Logs a check-in for today on the selected check-in habit
+fn done_today(s: &mut Cursive) {
+    let select = s.find_name::<SelectView<u64>>("habit_select").unwrap();
+    let habit_id = match select.selection() {
+        Some(habit_id) => *habit_id,
+        None => {
+            s.add_layer(Dialog::info("Nothing selected"));
+            return;
+        }
+    };
+
+    let app_data = s.user_data::<AppData>().unwrap();
+    let habit = app_data.user_data.find_habit(habit_id).unwrap();
+    if habit.kind != HabitKind::CheckIn {
+        s.add_layer(Dialog::info(
+            "Only check-in habits can be marked done. Open the habit to add a record.",
+        ));
+        return;
+    }
+
+    let name = habit.name.clone();
+    let today = SystemClock.today();
+    if habit.is_done_on(&today) {
+        s.add_layer(Dialog::info(format!("{} is already done today", name)));
+        return;
+    }
+
+    let edit = Edit::InsertRecord {
+        habit_id,
+        index: habit.records.len(),
+        record: Record {
+            id: new_id(),
+            note: String::new(),
+            date: today,
+            start_time: None,
+            end_time: None,
+            end_date: None,
+            amount: None,
+            pomodoro: false,
+        },
+    };
+    if !app::apply_edit(s, edit) {
+        return;
+    }
+    s.add_layer(Dialog::info(format!("{} done for today", name)));
+}
+
 fn delete_habit(s: &mut Cursive) {
     fn ok(s: &mut Cursive) {
-        let mut select = s.find_name::<SelectView<String>>("habit_select").unwrap();
-        let selected_id = select.selected_id().unwrap();
-        select.remove_item(selected_id);
-
-        let app_data = s.user_data::<AppData>().unwrap();
-        let user_data = &mut app_data.user_data;
-        user_data.habits.remove(selected_id);
-        app_data.unsaved_changes = true;
+        let mut select = s.find_name::<SelectView<u64>>("habit_select").unwrap();
+        let selected_index = select.selected_id().unwrap();
+        let habit_id = *select.selection().unwrap();
+        if !app::apply_edit(s, Edit::RemoveHabit { id: habit_id }) {
+            return;
+        }
+        select.remove_item(selected_index);
 
         s.pop_layer();
     }
 
-    let select = s.find_name::<SelectView<String>>("habit_select").unwrap();
-    let selected_id = select.selected_id();
+    let select = s.find_name::<SelectView<u64>>("habit_select").unwrap();
+    let selection = select.selection();
     let app_data = s.user_data::<AppData>().unwrap();
     let user_data = &mut app_data.user_data;
-    match selected_id {
+    match selection {
         None => s.add_layer(Dialog::info("Nothing selected")),
-        Some(focus) => {
-            let habit_name = user_data.habits[focus].name.clone();
+        Some(habit_id) => {
+            let habit_name = user_data.find_habit(*habit_id).unwrap().name.clone();
             s.add_layer(
                 Dialog::around(
                     LinearLayout::horizontal()
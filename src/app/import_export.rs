@@ -0,0 +1,218 @@
+use cursive::menu;
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+use dirs::home_dir;
+
+use crate::app;
+use crate::app::AppData;
+use crate::interchange::{ImportReport, SOURCES};
+use crate::{AvalancheError, Edit, HabitKind, UserData};
+
+// The Import/Export menu on the habits page
+pub fn menu() -> menu::Tree {
+    let mut tree = menu::Tree::new()
+        .leaf("Export CSV", |s| {
+            ask_for_path(s, "Export CSV", "avalanche.csv", |s, path| {
+                export(s, path, UserData::export_csv)
+            })
+        })
+        .leaf("Export JSON", |s| {
+            ask_for_path(s, "Export JSON", "avalanche.json", |s, path| {
+                export(s, path, UserData::export_json)
+            })
+        })
+        .leaf("Export iCalendar", |s| {
+            ask_for_path(s, "Export iCalendar", "avalanche.ics", |s, path| {
+                export(s, path, UserData::export_ics)
+            })
+        })
+        .delimiter()
+        .leaf("Import JSON", |s| {
+            ask_for_path(s, "Import JSON", "avalanche.json", |s, path| {
+                import(s, path, UserData::import_json)
+            })
+        })
+        .leaf("Import iCalendar", |s| {
+            ask_for_path(s, "Import iCalendar", "avalanche.ics", |s, path| {
+                choose_habit(s, path)
+            })
+        })
+        .delimiter();
+    // Formats that merge records into habits by name
+    for source in SOURCES {
+        let title = format!("Import {}", source.name());
+        tree.add_leaf(title.clone(), move |s| {
+            ask_for_path(s, &title, source.file_name(), move |s, path| {
+                import(s, path, |data, path| data.import_from(source, path))
+            })
+        });
+    }
+    tree
+}
+
+fn default_path(file_name: &str) -> String {
+    match home_dir() {
+        Some(dir) => dir.join(file_name).to_string_lossy().to_string(),
+        None => String::from(file_name),
+    }
+}
+
+fn ask_for_path<F>(s: &mut Cursive, title: &str, file_name: &str, on_ok: F)
+where
+    F: 'static + Fn(&mut Cursive, &str) + Send + Sync + Clone,
+{
+    let on_submit = on_ok.clone();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("File:"))
+                .child(
+                    EditView::new()
+                        .content(default_path(file_name))
+                        .on_submit(move |s, path| {
+                            s.pop_layer();
+                            on_submit(s, path);
+                        })
+                        .with_name("path")
+                        .min_width(50),
+                ),
+        )
+        .title(title)
+        .button("Ok", move |s| {
+            let path = s
+                .call_on_name("path", |view: &mut EditView| view.get_content())
+                .unwrap();
+            s.pop_layer();
+            on_ok(s, &path);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+fn export<F>(s: &mut Cursive, path: &str, write: F)
+where
+    F: Fn(&UserData, &str) -> Result<(), AvalancheError>,
+{
+    let app_data = s.user_data::<AppData>().unwrap();
+    let record_count = app_data.user_data.record_count();
+    match write(&app_data.user_data, path) {
+        Ok(()) => s.add_layer(Dialog::info(format!(
+            "Exported {} records to {}",
+            record_count, path
+        ))),
+        Err(error) => s.add_layer(Dialog::info(format!("Failed to export:\n{}", error))),
+    }
+}
+
+// Events in an iCalendar file all go to one timed habit
+fn choose_habit(s: &mut Cursive, path: &str) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let habits: Vec<_> = app_data
+        .user_data
+        .habits
+        .iter()
+        .filter(|habit| habit.kind == HabitKind::Timed)
+        .collect();
+    if habits.is_empty() {
+        s.add_layer(Dialog::info("Add a timed habit to import into first"));
+        return;
+    }
+    let mut select = SelectView::new();
+    for habit in &habits {
+        select.add_item(habit.name.clone(), habit.id);
+    }
+    if let Some(index) = habits
+        .iter()
+        .position(|habit| Some(habit.id) == app_data.selected_habit)
+    {
+        select.set_selection(index);
+    }
+
+    let path = String::from(path);
+    s.add_layer(
+        Dialog::around(select.on_submit(move |s, habit_id: &u64| {
+            let habit_id = *habit_id;
+            s.pop_layer();
+            import(s, &path, |data, path| data.import_ics(path, habit_id));
+        }))
+        .title("Import into")
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+/* Imports into a copy of the data first, so the report can be looked over
+ * before anything changes. The import is a single edit that can be undone.
+ */
+fn import<F>(s: &mut Cursive, path: &str, read: F)
+where
+    F: Fn(&mut UserData, &str) -> Result<ImportReport, AvalancheError>,
+{
+    let mut imported = s.user_data::<AppData>().unwrap().user_data.clone();
+    let report = match read(&mut imported, path) {
+        Ok(report) => report,
+        Err(error) => {
+            s.add_layer(Dialog::info(format!("Failed to import:\n{}", error)));
+            return;
+        }
+    };
+
+    let mut summary = format!("{} records can be imported.", report.imported);
+    if report.duplicates > 0 {
+        summary += &format!(
+            "\n{} are already there and will be skipped.",
+            report.duplicates
+        );
+    }
+    // With the kind each was guessed to be, so it can be checked first
+    if !report.new_habits.is_empty() {
+        let new_habits: Vec<String> = report
+            .new_habits
+            .iter()
+            .map(|name| {
+                let kind = imported
+                    .find_habit_by_name(name)
+                    .and_then(|id| imported.find_habit(id))
+                    .map(|habit| &habit.kind);
+                match kind {
+                    Some(HabitKind::Timed) => format!("{} (timed)", name),
+                    Some(HabitKind::Quit) => format!("{} (quit)", name),
+                    Some(HabitKind::Quantity { unit }) => format!("{} (amounts in {})", name, unit),
+                    Some(HabitKind::CheckIn) => format!("{} (check-in)", name),
+                    None => name.clone(),
+                }
+            })
+            .collect();
+        summary += &format!("\nNew habits: {}", new_habits.join(", "));
+    }
+    if !report.errors.is_empty() {
+        summary += &format!("\n\n{} rows will be skipped:", report.errors.len());
+    }
+
+    let mut dialog = Dialog::around(
+        LinearLayout::vertical()
+            .child(TextView::new(summary))
+            .child(TextView::new(report.describe_errors()).scrollable()),
+    )
+    .title("Import");
+    if report.imported > 0 {
+        dialog = dialog.button("Import", move |s| {
+            s.pop_layer();
+            if app::apply_edit(s, Edit::ReplaceAll(imported.clone())) {
+                app::habits_page::fill_habit_select(s);
+            }
+        });
+    }
+    s.add_layer(
+        dialog
+            .button("Cancel", |s| {
+                s.pop_layer();
+            })
+            .max_width(80)
+            .max_height(30),
+    );
+}
@@ -0,0 +1,195 @@
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, NamedView, TextView};
+use cursive::Cursive;
+
+use crate::app;
+use crate::app::AppData;
+use crate::{Edit, HabitKind, SystemClock};
+use AVALANCHE::pomodoro::{Phase, Pomodoro};
+use AVALANCHE::timer;
+
+// Shows the countdown on the records page. Updated every second by tick.
+pub fn view() -> NamedView<TextView> {
+    TextView::new("").with_name("pomodoro_view")
+}
+
+// Asks for the interval lengths and starts a session for the open habit, or
+// stops the running one
+pub fn toggle(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let habit = app_data.current_habit().clone();
+    match &app_data.pomodoro {
+        Some(session) if session.habit_id == habit.id => {
+            stop(s);
+            return;
+        }
+        Some(session) => {
+            let name = app_data
+                .user_data
+                .find_habit(session.habit_id)
+                .map_or(String::from("another habit"), |habit| habit.name.clone());
+            s.add_layer(Dialog::info(format!(
+                "The pomodoro for {} is running. Stop it first.",
+                name
+            )));
+            return;
+        }
+        None => (),
+    }
+    if habit.kind != HabitKind::Timed {
+        s.add_layer(Dialog::info("Only timed habits have pomodoros"));
+        return;
+    }
+    if app_data.read_only {
+        s.add_layer(Dialog::info("Opened read-only, so nothing can be changed"));
+        return;
+    }
+    if app_data.user_data.timer.is_some() {
+        s.add_layer(Dialog::info("A timer is running. Stop it first."));
+        return;
+    }
+
+    let minutes_field = |label: &str, name: &str, minutes: u32| {
+        LinearLayout::horizontal()
+            .child(TextView::new(label))
+            .child(
+                EditView::new()
+                    .content(minutes.to_string())
+                    .with_name(name)
+                    .fixed_width(5),
+            )
+            .child(TextView::new(" minutes"))
+    };
+    let defaults = app_data.config.pomodoro.clone();
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(minutes_field(
+                    "Work:  ",
+                    "pomodoro_work",
+                    defaults.work_minutes,
+                ))
+                .child(minutes_field(
+                    "Break: ",
+                    "pomodoro_break",
+                    defaults.break_minutes,
+                )),
+        )
+        .title("Pomodoro")
+        .button("Start", move |s| {
+            let minutes = |s: &mut Cursive, name: &str| {
+                s.call_on_name(name, |view: &mut EditView| {
+                    view.get_content().trim().parse::<u32>().ok()
+                })
+                .flatten()
+                .filter(|minutes| (1..=1440).contains(minutes))
+            };
+            let (Some(work), Some(rest)) =
+                (minutes(s, "pomodoro_work"), minutes(s, "pomodoro_break"))
+            else {
+                s.add_layer(Dialog::info(
+                    "Lengths should be whole minutes, between 1 and 1440",
+                ));
+                return;
+            };
+            s.pop_layer();
+            let app_data = s.user_data::<AppData>().unwrap();
+            app_data.pomodoro = Some(Pomodoro::start(habit.id, work, rest, &SystemClock));
+            app::habits_page::fill_habit_select(s);
+            tick(s);
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+fn stop(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let completed = app_data
+        .pomodoro
+        .take()
+        .map_or(0, |session| session.completed);
+    app::habits_page::fill_habit_select(s);
+    tick(s);
+    s.add_layer(Dialog::info(format!(
+        "Pomodoro stopped after {} work interval{}",
+        completed,
+        if completed == 1 { "" } else { "s" }
+    )));
+}
+
+// Moves the session on when an interval is over, and updates the countdown
+pub fn tick(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let Some(session) = app_data.pomodoro.as_mut() else {
+        s.call_on_name("pomodoro_view", |view: &mut TextView| view.set_content(""));
+        return;
+    };
+    let (habit_id, ended) = (session.habit_id, session.phase);
+    if let Some(record) = session.advance(&SystemClock) {
+        let habit = app_data.user_data.find_habit(habit_id).cloned();
+        let hook = app_data.config.pomodoro.hook.clone();
+        boundary(ended, habit.as_ref().map_or("", |habit| &habit.name), hook);
+        if let Some(record) = record {
+            let logged = habit.is_some_and(|habit| {
+                app::apply_edit(
+                    s,
+                    Edit::InsertRecord {
+                        habit_id,
+                        index: habit.records.len(),
+                        record,
+                    },
+                )
+            });
+            // The habit is gone or can't be changed, so there's nowhere to
+            // log the rest of the session
+            if !logged {
+                s.user_data::<AppData>().unwrap().pomodoro = None;
+                app::habits_page::fill_habit_select(s);
+            } else if s.user_data::<AppData>().unwrap().selected_habit == Some(habit_id) {
+                app::records_page::redraw(s);
+            }
+        }
+    }
+
+    let app_data = s.user_data::<AppData>().unwrap();
+    let text = match (&app_data.pomodoro, app_data.selected_habit) {
+        (Some(session), Some(selected)) if session.habit_id == selected => format!(
+            "Pomodoro: {} {} left, {} done (press p to stop)",
+            session.phase.name(),
+            timer::format_seconds(session.remaining_seconds(&SystemClock)),
+            session.completed
+        ),
+        _ => String::new(),
+    };
+    s.call_on_name("pomodoro_view", |view: &mut TextView| {
+        view.set_content(text)
+    });
+}
+
+/* Rings the terminal bell when an interval ends, and runs the hook from the
+ * config if there is one, with the interval that ended in AVALANCHE_PHASE
+ * and the habit name in AVALANCHE_HABIT.
+ */
+fn boundary(ended: Phase, habit_name: &str, hook: Option<String>) {
+    print!("\x07");
+    io::stdout().flush().unwrap_or(());
+    if let Some(hook) = hook {
+        Command::new("sh")
+            .arg("-c")
+            .arg(hook)
+            .env("AVALANCHE_PHASE", ended.name())
+            .env("AVALANCHE_HABIT", habit_name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map(|mut child| {
+                thread::spawn(move || child.wait());
+            })
+            .unwrap_or(());
+    }
+}
@@ -0,0 +1,102 @@
+use cursive::traits::*;
+use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::Cursive;
+
+use crate::app;
+use crate::app::AppData;
+
+/* Lists the default data file and the profiles from the config. Picking one
+ * offers to save, then opens its data file in place of the open one.
+ */
+pub fn switch_profile(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let config = app_data.config.clone();
+    let current = app_data.data_file_name.clone();
+
+    let mut select = SelectView::<(Option<String>, String)>::new().on_submit(
+        |s, (profile, filename): &(Option<String>, String)| {
+            s.pop_layer();
+            open(s, filename.clone(), profile.clone());
+        },
+    );
+    let mut choices: Vec<(Option<String>, String)> = Vec::new();
+    if let Some(filename) = config.data_file_path() {
+        choices.push((None, filename));
+    }
+    for name in config.profiles.keys() {
+        if let Some(filename) = config.profile_file_path(name) {
+            choices.push((Some(name.clone()), filename));
+        }
+    }
+    for (profile, filename) in choices {
+        let label = format!(
+            "{} | {}{}",
+            profile.as_deref().unwrap_or("Default"),
+            filename,
+            if filename == current { " (open)" } else { "" }
+        );
+        select.add_item(label, (profile, filename));
+    }
+
+    let mut layout = LinearLayout::vertical().child(select.scrollable());
+    if config.profiles.is_empty() {
+        layout.add_child(TextView::new(
+            "\nProfiles are added in the config file, like\n\
+             profiles: { \"work\": \"~/work.ron\" }",
+        ));
+    }
+    s.add_layer(
+        Dialog::around(layout)
+            .title("Switch profile")
+            .dismiss_button("Cancel"),
+    );
+}
+
+// Asks for the path of a data file to open in place of the open one. Files
+// that don't exist yet start empty.
+pub fn open_file(s: &mut Cursive) {
+    let current = s.user_data::<AppData>().unwrap().data_file_name.clone();
+    s.add_layer(
+        Dialog::around(
+            EditView::new()
+                .content(current)
+                .on_submit(|s, _| submit(s))
+                .with_name("open_file")
+                .min_width(50),
+        )
+        .title("Open file")
+        .button("Open", submit)
+        .dismiss_button("Cancel"),
+    );
+
+    fn submit(s: &mut Cursive) {
+        let filename = s
+            .call_on_name("open_file", |view: &mut EditView| view.get_content())
+            .unwrap()
+            .trim()
+            .to_string();
+        if filename.is_empty() {
+            s.add_layer(Dialog::info("Enter the path of a data file"));
+            return;
+        }
+        s.pop_layer();
+        // It belongs to a profile if it's one of theirs
+        let config = &s.user_data::<AppData>().unwrap().config;
+        let profile = config
+            .profiles
+            .keys()
+            .find(|name| config.profile_file_path(name).as_ref() == Some(&filename))
+            .cloned();
+        open(s, filename, profile);
+    }
+}
+
+fn open(s: &mut Cursive, filename: String, profile: Option<String>) {
+    if filename == s.user_data::<AppData>().unwrap().data_file_name {
+        s.add_layer(Dialog::info("That data file is already open"));
+        return;
+    }
+    app::leave_data_file(s, "switch", move |s| {
+        app::switch_data_file(s, filename.clone(), profile.clone())
+    });
+}
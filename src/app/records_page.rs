@@ -1,16 +1,19 @@
 use std::error::Error;
-use std::sync::Arc;
 
 use cursive::traits::*;
-use cursive::views::{Dialog, EditView, LinearLayout, SelectView, TextView};
+use cursive::views::{Checkbox, Dialog, EditView, LinearLayout, OnEventView, SelectView, TextView};
 use cursive::Cursive;
 
 use crate::app;
 use crate::app::AppData;
-use crate::{Date, Habit, Record, Time};
-
-pub fn draw(s: &mut Cursive, name: &str) {
-    let record_select = SelectView::<Record>::new()
+use crate::{
+    new_id, Clock, Date, Edit, GoalProgress, Goals, Habit, HabitKind, Record, Schedule,
+    SystemClock, Time, ValidationError, Weekday,
+};
+use AVALANCHE::config::Config;
+
+pub fn draw(s: &mut Cursive, habit_id: &u64) {
+    let record_select = SelectView::<u64>::new()
         .on_submit(show_record_info)
         .with_name("record_select")
         .scrollable()
@@ -19,36 +22,114 @@ pub fn draw(s: &mut Cursive, name: &str) {
     let stats_dialog = Dialog::new().with_name("stats_dialog");
 
     let app_data = s.user_data::<AppData>().unwrap();
-    let user_data = &app_data.user_data;
-    app_data.selected_habit = user_data.find_habit_by_name(name);
-    let habit = user_data.habits[app_data.selected_habit.unwrap()].clone();
+    app_data.selected_habit = Some(*habit_id);
+    let habit = app_data.current_habit().clone();
 
+    // On the whole page, as the list can't take focus while it's empty
     s.add_layer(
-        Dialog::around(
-            LinearLayout::vertical()
-                .child(stats_dialog)
-                .child(record_select),
+        OnEventView::new(
+            Dialog::around(
+                LinearLayout::vertical()
+                    .child(app::timer::view())
+                    .child(app::pomodoro::view())
+                    .child(stats_dialog)
+                    .child(record_select),
+            )
+            .title(format!(
+                "{} view",
+                app::capitalized(record_noun(&habit.kind))
+            )),
         )
-        .title("Record view"),
+        .on_event('t', app::timer::toggle)
+        .on_event('p', app::pomodoro::toggle),
     );
 
     write_habit_stats(s, &habit);
+    fill_record_select(s, &habit);
+    draw_records_menubar(s, &habit.kind);
+    app::timer::tick(s);
+    app::pomodoro::tick(s);
+}
 
-    for record in &habit.records {
-        s.call_on_name("record_select", |view: &mut SelectView<Record>| {
-            view.add_item(record_item_builder(record), record.clone());
-        });
+// Shows the open habit again after an undo or redo, or leaves the page if
+// the habit is gone
+pub fn redraw(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let habit = match app_data
+        .user_data
+        .find_habit(app_data.selected_habit.unwrap())
+    {
+        Some(habit) => habit.clone(),
+        None => {
+            back(s);
+            return;
+        }
+    };
+    write_habit_stats(s, &habit);
+    fill_record_select(s, &habit);
+    app::timer::tick(s);
+}
+
+fn fill_record_select(s: &mut Cursive, habit: &Habit) {
+    let config = s.user_data::<AppData>().unwrap().config.clone();
+    s.call_on_name("record_select", |view: &mut SelectView<u64>| {
+        let selected = view.selection().map(|id| *id);
+        view.clear();
+        for record in &habit.records {
+            view.add_item(record_item_builder(record, &habit.kind, &config), record.id);
+        }
+        if let Some(index) = habit
+            .records
+            .iter()
+            .position(|record| Some(record.id) == selected)
+        {
+            view.set_selection(index);
+        }
+    });
+}
+
+// What a record means for this kind of habit, for use in labels
+fn record_noun(kind: &HabitKind) -> &'static str {
+    match kind {
+        HabitKind::Timed => "record",
+        HabitKind::Quit => "relapse",
+        HabitKind::Quantity { .. } => "record",
+        HabitKind::CheckIn => "check-in",
     }
+}
 
-    draw_records_menubar(s);
+fn selected_habit_kind(s: &mut Cursive) -> HabitKind {
+    let app_data = s.user_data::<AppData>().unwrap();
+    app_data.current_habit().kind.clone()
 }
 
-fn draw_records_menubar(s: &mut Cursive) {
+fn draw_records_menubar(s: &mut Cursive, kind: &HabitKind) {
     s.menubar().clear();
     s.menubar()
-        .add_leaf("Add record", add_record)
+        .add_leaf(format!("Add {}", record_noun(kind)), add_record)
         .add_delimiter()
         .add_leaf("Delete selection", delete_record)
+        .add_delimiter();
+    // Goals are durations, so they only apply to timed habits
+    if *kind == HabitKind::Timed {
+        s.menubar()
+            .add_leaf("Start/stop timer (t)", app::timer::toggle)
+            .add_delimiter()
+            .add_leaf("Pomodoro (p)", app::pomodoro::toggle)
+            .add_delimiter()
+            .add_leaf("Set goals", set_goals)
+            .add_delimiter();
+    }
+    // Schedules don't apply to habits being quit
+    if *kind != HabitKind::Quit {
+        s.menubar()
+            .add_leaf("Set schedule", set_schedule)
+            .add_delimiter();
+    }
+    s.menubar()
+        .add_leaf("Undo (Ctrl-Z)", app::undo)
+        .add_delimiter()
+        .add_leaf("Redo (Ctrl-Y)", app::redo)
         .add_delimiter()
         .add_leaf("Save", |s| {
             app::save_data(s).unwrap_or(());
@@ -60,36 +141,151 @@ fn draw_records_menubar(s: &mut Cursive) {
 }
 
 fn write_habit_stats(s: &mut Cursive, habit: &Habit) {
+    fn goal_line(label: &str, period_name: &str, progress: &Option<GoalProgress>) -> String {
+        match progress {
+            Some(progress) => format!(
+                "\n{} goal: {} of {} this {}{} | Met {} times, missed {} times",
+                label,
+                duration_string(&progress.current),
+                duration_string(&progress.target),
+                period_name,
+                if progress.is_met() { " (done)" } else { "" },
+                progress.met,
+                progress.missed
+            ),
+            None => String::new(),
+        }
+    }
+
+    let config = s.user_data::<AppData>().unwrap().config.clone();
     s.call_on_name("stats_dialog", |view: &mut Dialog| {
-        let stats = habit.get_stats();
+        let stats = habit.get_stats_with(&SystemClock, config.week_start);
+        if let Some(quit) = &stats.quit {
+            let days_since_relapse = match quit.days_since_relapse {
+                Some(days) => days.to_string(),
+                None => String::from("no relapses"),
+            };
+            let relapses_per_week = match quit.relapses_per_week {
+                Some(rate) => format!("{:.1}", rate),
+                None => String::from("-"),
+            };
+            view.set_content(TextView::new(format!(
+                "Days since last relapse: {} | Longest clean run: {} days\n\
+                 Relapses: {} ({} per week)",
+                days_since_relapse, quit.longest_clean_run, quit.relapse_count, relapses_per_week
+            )));
+            return;
+        }
+
+        let unit = habit.schedule.streak_unit();
+        let completion = match stats.completion_rate {
+            Some(rate) => format!("{:.0}%", rate * 100.0),
+            None => String::from("-"),
+        };
+        let longest_streak = match &stats.longest_streak {
+            Some(streak) => format!(
+                "{} {} ({} to {})",
+                streak.length,
+                unit,
+                config.format_date(&streak.start),
+                config.format_date(&streak.end)
+            ),
+            None => String::from("-"),
+        };
+        let totals = match &stats.quantity {
+            Some(quantity) => {
+                let today = quantity
+                    .per_day
+                    .iter()
+                    .find(|(date, _)| *date == SystemClock.today())
+                    .map_or(0.0, |(_, amount)| *amount);
+                let average = |average: Option<f64>| match average {
+                    Some(average) => format!("{:.1}", average),
+                    None => String::from("-"),
+                };
+                format!(
+                    "Today: {} {} | Total: {} {} | Average: {} per record, {} per day",
+                    today,
+                    quantity.unit,
+                    quantity.total,
+                    quantity.unit,
+                    average(quantity.average_per_record),
+                    average(quantity.average_per_day)
+                )
+            }
+            None if habit.kind == HabitKind::CheckIn => {
+                format!("Check-ins: {}", habit.records.len())
+            }
+            None => format!(
+                "Total time spent: {} hours and {} minutes | Pomodoros: {}",
+                stats.total_time.hours, stats.total_time.minutes, stats.pomodoros
+            ),
+        };
         view.set_content(TextView::new(format!(
-            "Most recent streak: {} days \
-                     | Total time spent: {} hours and {} minutes",
-            stats.streak_length, stats.total_time.hours, stats.total_time.minutes
-        )))
+            "Schedule: {} | Completion: {}\n\
+             Current streak: {} {}{} | Longest streak: {}\n\
+             {}{}{}{}",
+            habit.schedule.describe(),
+            completion,
+            stats.current_streak,
+            unit,
+            if stats.streak_at_risk {
+                " (not done today yet)"
+            } else {
+                ""
+            },
+            longest_streak,
+            totals,
+            goal_line("Daily", "day", &stats.daily_goal),
+            goal_line("Weekly", "week", &stats.weekly_goal),
+            goal_line("Monthly", "month", &stats.monthly_goal)
+        )));
     });
 }
 
-fn record_item_builder(record: &Record) -> String {
-    format!(
-        "{}-{}-{}: {:02}:{:02} - {:02}:{:02} | {}",
-        record.date.year,
-        record.date.month,
-        record.date.day,
-        record.start_time.hours,
-        record.start_time.minutes,
-        record.end_time.hours,
-        record.end_time.minutes,
-        record.note
-    )
+fn duration_string(time: &Time) -> String {
+    format!("{}h {:02}m", time.hours, time.minutes)
+}
+
+fn record_item_builder(record: &Record, kind: &HabitKind, config: &Config) -> String {
+    let mut item = config.format_date(&record.date);
+
+    match (&record.start_time, &record.end_time) {
+        (Some(start_time), Some(end_time))
+            if start_time != end_time || record.end_date.is_some() =>
+        {
+            // Only show the end date when the record ends on a different day
+            let end_date = match &record.end_date {
+                Some(end_date) if *end_date != record.date => {
+                    format!("{} ", config.format_date(end_date))
+                }
+                _ => String::new(),
+            };
+            item += &format!(
+                ": {} - {}{}",
+                config.format_time(start_time),
+                end_date,
+                config.format_time(end_time)
+            );
+        }
+        (Some(time), _) => {
+            item += &format!(": {}", config.format_time(time));
+        }
+        _ => {}
+    }
+
+    if let Some(amount) = record.amount {
+        match kind {
+            HabitKind::Quantity { unit } => item += &format!(": {} {}", amount, unit),
+            _ => item += &format!(": {}", amount),
+        }
+    }
+
+    format!("{} | {}", item, record.note)
 }
 
-fn record_item_builder_dialog<F>(
-    s: &mut Cursive,
-    title: String,
-    on_ok: F,
-    based_on: Option<Arc<Record>>,
-) where
+fn record_item_builder_dialog<F>(s: &mut Cursive, title: String, on_ok: F, based_on: Option<Record>)
+where
     F: 'static + Fn(&mut Cursive, Record) + Send + Sync,
 {
     fn time_from_strings(
@@ -102,9 +298,9 @@ fn record_item_builder_dialog<F>(
         };
 
         if time.is_valid_time_of_day() {
-            return Ok(time);
+            Ok(time)
         } else {
-            return Err("Invalid time".into());
+            Err(ValidationError::InvalidTime(time).into())
         }
     }
 
@@ -120,310 +316,360 @@ fn record_item_builder_dialog<F>(
         };
 
         if date.is_valid() {
-            return Ok(date);
+            Ok(date)
         } else {
-            return Err("Invalid date".into());
+            Err(ValidationError::InvalidDate(date).into())
         }
     }
 
-    fn parse_date(s: &mut Cursive) -> Result<Date, Box<dyn Error>> {
-        let date_year = s
-            .call_on_name("date_year", |view: &mut EditView| {
-                view.get_content().to_string()
-            })
-            .unwrap();
-        let date_month = s
-            .call_on_name("date_month", |view: &mut EditView| {
-                view.get_content().to_string()
-            })
-            .unwrap();
-        let date_day = s
-            .call_on_name("date_day", |view: &mut EditView| {
-                view.get_content().to_string()
-            })
-            .unwrap();
-
-        return date_from_strings(date_year, date_month, date_day);
+    fn read_field(s: &mut Cursive, name: &str) -> String {
+        s.call_on_name(name, |view: &mut EditView| view.get_content().to_string())
+            .unwrap()
     }
 
-    fn parse_start_time(s: &mut Cursive) -> Result<Time, Box<dyn Error>> {
-        let start_time_hours = s
-            .call_on_name("start_time_hours", |view: &mut EditView| {
-                view.get_content().to_string()
-            })
-            .unwrap();
-        let start_time_minutes = s
-            .call_on_name("start_time_minutes", |view: &mut EditView| {
-                view.get_content().to_string()
-            })
+    fn set_field(s: &mut Cursive, name: &str, content: String) {
+        s.call_on_name(name, |view: &mut EditView| view.set_content(content))
             .unwrap();
+    }
 
-        return time_from_strings(start_time_hours, start_time_minutes);
+    fn parse_date(s: &mut Cursive) -> Result<Date, Box<dyn Error>> {
+        date_from_strings(
+            read_field(s, "date_year"),
+            read_field(s, "date_month"),
+            read_field(s, "date_day"),
+        )
     }
 
-    fn parse_end_time(s: &mut Cursive) -> Result<Time, Box<dyn Error>> {
-        let end_time_hours = s
-            .call_on_name("end_time_hours", |view: &mut EditView| {
-                view.get_content().to_string()
-            })
-            .unwrap();
-        let end_time_minutes = s
-            .call_on_name("end_time_minutes", |view: &mut EditView| {
-                view.get_content().to_string()
-            })
-            .unwrap();
+    // An empty end date means the record ends on the day it starts
+    fn parse_end_date(s: &mut Cursive) -> Result<Option<Date>, Box<dyn Error>> {
+        let date_year = read_field(s, "end_date_year");
+        let date_month = read_field(s, "end_date_month");
+        let date_day = read_field(s, "end_date_day");
+        if date_year.is_empty() && date_month.is_empty() && date_day.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(date_from_strings(date_year, date_month, date_day)?))
+    }
 
-        return time_from_strings(end_time_hours, end_time_minutes);
+    fn parse_time(s: &mut Cursive, prefix: &str) -> Result<Time, Box<dyn Error>> {
+        time_from_strings(
+            read_field(s, &format!("{}_hours", prefix)),
+            read_field(s, &format!("{}_minutes", prefix)),
+        )
     }
 
-    fn is_start_before_end(start: &Time, end: &Time) -> bool {
-        let start_minutes: u16 = start.hours * 60 + start.minutes;
-        let end_minutes: u16 = end.hours * 60 + end.minutes;
-        if end_minutes < start_minutes {
-            return false;
+    fn parse_amount(s: &mut Cursive) -> Result<f64, Box<dyn Error>> {
+        let amount: f64 = read_field(s, "amount").trim().parse()?;
+        if amount.is_finite() && amount >= 0.0 {
+            Ok(amount)
         } else {
-            return true;
+            Err("Invalid amount".into())
         }
     }
 
-    // Build UI for entering record info
-    let date_row = LinearLayout::horizontal()
-        .child(
-            EditView::new()
-                .max_content_width(4)
-                .with_name("date_year")
-                .fixed_width(5),
-        )
-        .child(TextView::new("-"))
-        .child(
-            EditView::new()
-                .max_content_width(2)
-                .with_name("date_month")
-                .fixed_width(3),
-        )
-        .child(TextView::new("-"))
-        .child(
-            EditView::new()
-                .max_content_width(2)
-                .with_name("date_day")
-                .fixed_width(3),
-        );
+    fn date_row(prefix: &str) -> LinearLayout {
+        LinearLayout::horizontal()
+            .child(
+                EditView::new()
+                    .max_content_width(4)
+                    .with_name(format!("{}_year", prefix))
+                    .fixed_width(5),
+            )
+            .child(TextView::new("-"))
+            .child(
+                EditView::new()
+                    .max_content_width(2)
+                    .with_name(format!("{}_month", prefix))
+                    .fixed_width(3),
+            )
+            .child(TextView::new("-"))
+            .child(
+                EditView::new()
+                    .max_content_width(2)
+                    .with_name(format!("{}_day", prefix))
+                    .fixed_width(3),
+            )
+    }
 
-    let start_time_row = LinearLayout::horizontal()
-        .child(
-            EditView::new()
-                .max_content_width(2)
-                .with_name("start_time_hours")
-                .fixed_width(3),
-        )
-        .child(TextView::new(":"))
-        .child(
-            EditView::new()
-                .max_content_width(2)
-                .with_name("start_time_minutes")
-                .fixed_width(3),
-        );
+    fn time_row(prefix: &str) -> LinearLayout {
+        LinearLayout::horizontal()
+            .child(
+                EditView::new()
+                    .max_content_width(2)
+                    .with_name(format!("{}_hours", prefix))
+                    .fixed_width(3),
+            )
+            .child(TextView::new(":"))
+            .child(
+                EditView::new()
+                    .max_content_width(2)
+                    .with_name(format!("{}_minutes", prefix))
+                    .fixed_width(3),
+            )
+    }
 
-    let end_time_row = LinearLayout::horizontal()
-        .child(
-            EditView::new()
-                .max_content_width(2)
-                .with_name("end_time_hours")
-                .fixed_width(3),
-        )
-        .child(TextView::new(":"))
-        .child(
-            EditView::new()
-                .max_content_width(2)
-                .with_name("end_time_minutes")
-                .fixed_width(3),
-        );
+    // Which fields are shown depends on the kind of habit
+    let kind = selected_habit_kind(s);
 
+    // Build UI for entering record info
+    let mut layout = LinearLayout::vertical()
+        .child(TextView::new("Date:"))
+        .child(date_row("date"));
+    match &kind {
+        HabitKind::Timed => {
+            layout.add_child(TextView::new("Start Time:"));
+            layout.add_child(time_row("start_time"));
+            layout.add_child(TextView::new("End Date (empty if same day):"));
+            layout.add_child(date_row("end_date"));
+            layout.add_child(TextView::new("End Time:"));
+            layout.add_child(time_row("end_time"));
+        }
+        HabitKind::Quit => {
+            // Relapses happen at a point in time, so they only get one time
+            layout.add_child(TextView::new("Time:"));
+            layout.add_child(time_row("start_time"));
+        }
+        HabitKind::Quantity { unit } => {
+            layout.add_child(TextView::new(format!("Amount ({}):", unit)));
+            layout.add_child(EditView::new().with_name("amount").fixed_width(10));
+        }
+        HabitKind::CheckIn => {}
+    }
+    layout.add_child(TextView::new("Note:"));
+    layout.add_child(EditView::new().with_name("note").fixed_width(30));
+
+    let ok_kind = kind.clone();
+    // Edited records keep their ID. New ones get one when they're added.
+    let ok_id = based_on.as_ref().map_or(0, |record| record.id);
+    let ok_pomodoro = based_on.as_ref().is_some_and(|record| record.pomodoro);
     s.add_layer(
-        Dialog::around(
-            LinearLayout::vertical()
-                .child(TextView::new("Date:"))
-                .child(date_row)
-                .child(TextView::new("Start Time:"))
-                .child(start_time_row)
-                .child(TextView::new("End Time:"))
-                .child(end_time_row)
-                .child(TextView::new("Note:"))
-                .child(EditView::new().with_name("note").fixed_width(30)),
-        )
-        .title(title)
-        .button("Ok", move |s| {
-            let date: Date;
-            match parse_date(s) {
-                Ok(result) => {
-                    date = result;
-                }
-                Err(_) => {
-                    s.add_layer(Dialog::info("Failed to parse date"));
-                    return;
+        Dialog::around(layout)
+            .title(title)
+            .button("Ok", move |s| {
+                let date = match parse_date(s) {
+                    Ok(result) => result,
+                    Err(error) => {
+                        s.add_layer(Dialog::info(format!("Failed to parse date: {}", error)));
+                        return;
+                    }
+                };
+
+                let note = read_field(s, "note");
+                let mut record = Record {
+                    id: ok_id,
+                    note,
+                    date,
+                    start_time: None,
+                    end_time: None,
+                    end_date: None,
+                    amount: None,
+                    pomodoro: ok_pomodoro,
+                };
+
+                match ok_kind {
+                    HabitKind::CheckIn => {
+                        on_ok(s, record);
+                        return;
+                    }
+                    HabitKind::Quantity { .. } => {
+                        match parse_amount(s) {
+                            Ok(result) => record.amount = Some(result),
+                            Err(error) => {
+                                s.add_layer(Dialog::info(format!(
+                                    "Failed to parse amount: {}",
+                                    error
+                                )));
+                                return;
+                            }
+                        }
+                        on_ok(s, record);
+                        return;
+                    }
+                    _ => {}
                 }
-            }
 
-            let start_time: Time;
-            match parse_start_time(s) {
-                Ok(result) => {
-                    start_time = result;
-                }
-                Err(_) => {
-                    s.add_layer(Dialog::info("Failed to parse start time"));
-                    return;
+                match parse_time(s, "start_time") {
+                    Ok(result) => record.start_time = Some(result),
+                    Err(error) => {
+                        s.add_layer(Dialog::info(format!(
+                            "Failed to parse start time: {}",
+                            error
+                        )));
+                        return;
+                    }
                 }
-            }
 
-            let end_time: Time;
-            match parse_end_time(s) {
-                Ok(result) => {
-                    end_time = result;
+                if ok_kind == HabitKind::Timed {
+                    match parse_time(s, "end_time") {
+                        Ok(result) => record.end_time = Some(result),
+                        Err(error) => {
+                            s.add_layer(Dialog::info(format!(
+                                "Failed to parse end time: {}",
+                                error
+                            )));
+                            return;
+                        }
+                    }
+
+                    match parse_end_date(s) {
+                        Ok(result) => record.end_date = result,
+                        Err(error) => {
+                            s.add_layer(Dialog::info(format!(
+                                "Failed to parse end date: {}",
+                                error
+                            )));
+                            return;
+                        }
+                    }
                 }
-                Err(_) => {
-                    s.add_layer(Dialog::info("Failed to parse end time"));
+
+                if let Err(error) = record.validate() {
+                    s.add_layer(Dialog::info(error.to_string()));
                     return;
                 }
-            }
-
-            if !is_start_before_end(&start_time, &end_time) {
-                s.add_layer(Dialog::info("Start time should be before end time"));
-                return;
-            }
 
-            let note = s
-                .call_on_name("note", |view: &mut EditView| view.get_content().to_string())
-                .unwrap();
-
-            let record = Record {
-                note: note,
-                date: date,
-                start_time: start_time,
-                end_time: end_time,
-            };
-
-            on_ok(s, record);
-        })
-        .button("Cancel", |s| {
-            s.pop_layer();
-        }),
+                on_ok(s, record);
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
     );
 
     // If editing a record, populate fields with existing info
     if let Some(record) = based_on {
-        s.call_on_name("date_year", |view: &mut EditView| {
-            view.set_content(record.date.year.to_string())
-        })
-        .unwrap();
-        s.call_on_name("date_month", |view: &mut EditView| {
-            view.set_content(record.date.month.to_string())
-        })
-        .unwrap();
-        s.call_on_name("date_day", |view: &mut EditView| {
-            view.set_content(record.date.day.to_string())
-        })
-        .unwrap();
-        s.call_on_name("start_time_hours", |view: &mut EditView| {
-            view.set_content(record.start_time.hours.to_string())
-        })
-        .unwrap();
-        s.call_on_name("start_time_minutes", |view: &mut EditView| {
-            view.set_content(record.start_time.minutes.to_string())
-        })
-        .unwrap();
-        s.call_on_name("end_time_hours", |view: &mut EditView| {
-            view.set_content(record.end_time.hours.to_string())
-        })
-        .unwrap();
-        s.call_on_name("end_time_minutes", |view: &mut EditView| {
-            view.set_content(record.end_time.minutes.to_string())
-        })
-        .unwrap();
-        s.call_on_name("note", |view: &mut EditView| {
-            view.set_content(record.note.clone())
-        })
-        .unwrap();
+        set_field(s, "date_year", record.date.year.to_string());
+        set_field(s, "date_month", record.date.month.to_string());
+        set_field(s, "date_day", record.date.day.to_string());
+        if kind == HabitKind::Timed {
+            if let Some(end_date) = &record.end_date {
+                set_field(s, "end_date_year", end_date.year.to_string());
+                set_field(s, "end_date_month", end_date.month.to_string());
+                set_field(s, "end_date_day", end_date.day.to_string());
+            }
+            if let Some(end_time) = &record.end_time {
+                set_field(s, "end_time_hours", end_time.hours.to_string());
+                set_field(s, "end_time_minutes", end_time.minutes.to_string());
+            }
+        }
+        if matches!(kind, HabitKind::Timed | HabitKind::Quit) {
+            if let Some(start_time) = &record.start_time {
+                set_field(s, "start_time_hours", start_time.hours.to_string());
+                set_field(s, "start_time_minutes", start_time.minutes.to_string());
+            }
+        }
+        if let (HabitKind::Quantity { .. }, Some(amount)) = (&kind, record.amount) {
+            set_field(s, "amount", amount.to_string());
+        }
+        set_field(s, "note", record.note.clone());
     }
 }
 
-fn show_record_info(s: &mut Cursive, record: &Record) {
+fn show_record_info(s: &mut Cursive, record_id: &u64) {
     fn edit_record(s: &mut Cursive, record: Record) {
-        let mut record_select = s.find_name::<SelectView<Record>>("record_select").unwrap();
-        let selected_id = record_select.selected_id().unwrap();
+        let mut record_select = s.find_name::<SelectView<u64>>("record_select").unwrap();
+        let selected_index = record_select.selected_id().unwrap();
+        let habit_id = s.user_data::<AppData>().unwrap().selected_habit.unwrap();
+        if !app::apply_edit(
+            s,
+            Edit::ReplaceRecord {
+                habit_id,
+                record: record.clone(),
+            },
+        ) {
+            return;
+        }
         let app_data = s.user_data::<AppData>().unwrap();
-        let user_data = &mut app_data.user_data;
-        let habit_id = app_data.selected_habit.unwrap();
-        user_data.habits[habit_id].records[selected_id] = record.clone();
-        app_data.unsaved_changes = true;
+        let (habit, config) = (app_data.current_habit().clone(), app_data.config.clone());
 
-        record_select.remove_item(selected_id);
+        record_select.remove_item(selected_index);
         record_select.insert_item(
-            selected_id,
-            record_item_builder(&user_data.habits[habit_id].records[selected_id]),
-            user_data.habits[habit_id].records[selected_id].clone(),
+            selected_index,
+            record_item_builder(&record, &habit.kind, &config),
+            record.id,
         );
-        record_select.set_selection(selected_id);
+        record_select.set_selection(selected_index);
 
-        let habit = user_data.habits[habit_id].clone();
         write_habit_stats(s, &habit);
         s.pop_layer();
     }
 
-    let info_dialog = Dialog::around(TextView::new(record_item_builder(&record)))
-        .button("Edit", |s| {
-            let record_select = s.find_name::<SelectView<Record>>("record_select").unwrap();
-            let record = record_select.selection().unwrap();
-            s.pop_layer();
-            record_item_builder_dialog(s, String::from("Edit record"), edit_record, Some(record));
-        })
-        .button("Done", |s| {
-            s.pop_layer();
-        });
+    let app_data = s.user_data::<AppData>().unwrap();
+    let habit = app_data.current_habit();
+    let record = habit.find_record(*record_id).unwrap().clone();
+    let info_dialog = Dialog::around(TextView::new(record_item_builder(
+        &record,
+        &habit.kind,
+        &app_data.config,
+    )))
+    .button("Edit", move |s| {
+        let title = format!("Edit {}", record_noun(&selected_habit_kind(s)));
+        s.pop_layer();
+        record_item_builder_dialog(s, title, edit_record, Some(record.clone()));
+    })
+    .button("Done", |s| {
+        s.pop_layer();
+    });
 
     s.add_layer(info_dialog);
 }
 
 fn add_record(s: &mut Cursive) {
-    fn add_to_list(s: &mut Cursive, record: Record) {
-        s.call_on_name("record_select", |view: &mut SelectView<Record>| {
-            view.add_item(record_item_builder(&record), record.clone());
-        });
-
+    fn add_to_list(s: &mut Cursive, mut record: Record) {
         let app_data = s.user_data::<AppData>().unwrap();
-        let user_data = &mut app_data.user_data;
         let habit_id = app_data.selected_habit.unwrap();
+        let index = app_data.current_habit().records.len();
+        let id = new_id();
+        record.id = id;
+        if !app::apply_edit(
+            s,
+            Edit::InsertRecord {
+                habit_id,
+                index,
+                record: record.clone(),
+            },
+        ) {
+            return;
+        }
+        let app_data = s.user_data::<AppData>().unwrap();
+        let (habit, config) = (app_data.current_habit().clone(), app_data.config.clone());
 
-        user_data.habits[habit_id].records.push(record);
-        app_data.unsaved_changes = true;
-
-        let habit = user_data.habits[habit_id].clone();
+        s.call_on_name("record_select", |view: &mut SelectView<u64>| {
+            view.add_item(record_item_builder(&record, &habit.kind, &config), id);
+        });
 
         s.pop_layer();
         write_habit_stats(s, &habit);
     }
 
-    record_item_builder_dialog(s, String::from("New record"), add_to_list, None);
+    let title = format!("New {}", record_noun(&selected_habit_kind(s)));
+    record_item_builder_dialog(s, title, add_to_list, None);
 }
 
 fn delete_record(s: &mut Cursive) {
     fn ok(s: &mut Cursive) {
-        let mut record_select = s.find_name::<SelectView<Record>>("record_select").unwrap();
-        let selected_id = record_select.selected_id().unwrap();
-        let app_data = s.user_data::<AppData>().unwrap();
-        let user_data = &mut app_data.user_data;
-        let habit_id = app_data.selected_habit.unwrap();
-
-        user_data.habits[habit_id].records.remove(selected_id);
-        record_select.remove_item(selected_id);
-        app_data.unsaved_changes = true;
+        let mut record_select = s.find_name::<SelectView<u64>>("record_select").unwrap();
+        let selected_index = record_select.selected_id().unwrap();
+        let record_id = *record_select.selection().unwrap();
+        let habit_id = s.user_data::<AppData>().unwrap().selected_habit.unwrap();
+        if !app::apply_edit(
+            s,
+            Edit::RemoveRecord {
+                habit_id,
+                id: record_id,
+            },
+        ) {
+            return;
+        }
+        let habit = s.user_data::<AppData>().unwrap().current_habit().clone();
+        record_select.remove_item(selected_index);
 
-        let habit = user_data.habits[habit_id].clone();
         write_habit_stats(s, &habit);
         s.pop_layer();
     }
 
-    let record_select = s.find_name::<SelectView<Record>>("record_select").unwrap();
+    let record_select = s.find_name::<SelectView<u64>>("record_select").unwrap();
     let selected_id = record_select.selected_id();
+    let noun = record_noun(&selected_habit_kind(s));
     match selected_id {
         None => s.add_layer(Dialog::info("Nothing selected")),
         Some(_) => {
@@ -433,12 +679,201 @@ fn delete_record(s: &mut Cursive) {
                     .button("No", |s| {
                         s.pop_layer();
                     })
-                    .title("Delete record?"),
+                    .title(format!("Delete {}?", noun)),
             );
         }
     }
 }
 
+fn set_goals(s: &mut Cursive) {
+    // Goals are entered in minutes. An empty field means no goal.
+    fn parse_goal(s: &mut Cursive, name: &str) -> Result<Option<Time>, Box<dyn Error>> {
+        let content = s
+            .call_on_name(name, |view: &mut EditView| view.get_content().to_string())
+            .unwrap();
+        let content = content.trim();
+        if content.is_empty() {
+            return Ok(None);
+        }
+        let minutes: u32 = content.parse()?;
+        if minutes == 0 {
+            return Ok(None);
+        }
+        Ok(Some(Time::from_minutes(minutes).ok_or("Too many minutes")?))
+    }
+
+    fn goal_row(label: &str, name: &str, goal: &Option<Time>) -> LinearLayout {
+        let content = match goal {
+            Some(goal) => goal.as_minutes().to_string(),
+            None => String::new(),
+        };
+        LinearLayout::horizontal()
+            .child(TextView::new(label).fixed_width(10))
+            .child(
+                EditView::new()
+                    .content(content)
+                    .max_content_width(6)
+                    .with_name(name)
+                    .fixed_width(7),
+            )
+            .child(TextView::new(" minutes"))
+    }
+
+    let app_data = s.user_data::<AppData>().unwrap();
+    let goals = app_data.current_habit().goals.clone();
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("Leave empty for no goal"))
+                .child(goal_row("Daily:", "goal_daily", &goals.daily))
+                .child(goal_row("Weekly:", "goal_weekly", &goals.weekly))
+                .child(goal_row("Monthly:", "goal_monthly", &goals.monthly)),
+        )
+        .title("Set goals")
+        .button("Ok", |s| {
+            let mut goals = Goals::default();
+            for (name, goal) in [
+                ("goal_daily", &mut goals.daily),
+                ("goal_weekly", &mut goals.weekly),
+                ("goal_monthly", &mut goals.monthly),
+            ] {
+                match parse_goal(s, name) {
+                    Ok(result) => *goal = result,
+                    Err(_) => {
+                        s.add_layer(Dialog::info("Goals should be whole numbers of minutes"));
+                        return;
+                    }
+                }
+            }
+
+            let app_data = s.user_data::<AppData>().unwrap();
+            let mut habit = app_data.current_habit().clone();
+            habit.goals = goals;
+            if !app::apply_edit(s, Edit::ReplaceHabit(habit.clone())) {
+                return;
+            }
+
+            s.pop_layer();
+            write_habit_stats(s, &habit);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+fn set_schedule(s: &mut Cursive) {
+    fn parse_schedule(s: &mut Cursive) -> Result<Schedule, Box<dyn Error>> {
+        let kind = s
+            .call_on_name("schedule_kind", |view: &mut SelectView<u8>| {
+                view.selection().map(|kind| *kind)
+            })
+            .unwrap()
+            .unwrap_or(0);
+        let count = s
+            .call_on_name("schedule_count", |view: &mut EditView| {
+                view.get_content().to_string()
+            })
+            .unwrap();
+
+        match kind {
+            1 => {
+                let mut weekdays = Vec::new();
+                for weekday in Weekday::ALL {
+                    let checked = s
+                        .call_on_name(weekday.short_name(), |view: &mut Checkbox| {
+                            view.is_checked()
+                        })
+                        .unwrap();
+                    if checked {
+                        weekdays.push(weekday);
+                    }
+                }
+                if weekdays.is_empty() {
+                    return Err("No weekdays selected".into());
+                }
+                Ok(Schedule::Weekdays(weekdays))
+            }
+            2 => match count.trim().parse()? {
+                0 => Err("Interval must be at least 1 day".into()),
+                days => Ok(Schedule::EveryNDays(days)),
+            },
+            3 => match count.trim().parse()? {
+                times @ 1..=7 => Ok(Schedule::TimesPerWeek(times)),
+                _ => Err("Times per week must be between 1 and 7".into()),
+            },
+            _ => Ok(Schedule::Daily),
+        }
+    }
+
+    let app_data = s.user_data::<AppData>().unwrap();
+    let schedule = app_data.current_habit().schedule.clone();
+    let week_start = app_data.config.week_start.number_from_monday();
+
+    let (selected_kind, count, weekdays) = match &schedule {
+        Schedule::Daily => (0, String::new(), Vec::new()),
+        Schedule::Weekdays(weekdays) => (1, String::new(), weekdays.clone()),
+        Schedule::EveryNDays(days) => (2, days.to_string(), Vec::new()),
+        Schedule::TimesPerWeek(times) => (3, times.to_string(), Vec::new()),
+    };
+
+    let mut kind_select = SelectView::<u8>::new().popup();
+    kind_select.add_item("Every day", 0);
+    kind_select.add_item("On these weekdays", 1);
+    kind_select.add_item("Every N days", 2);
+    kind_select.add_item("N times per week", 3);
+    kind_select.set_selection(selected_kind);
+
+    let mut weekday_row = LinearLayout::horizontal();
+    for weekday in (week_start..week_start + 7).map(Weekday::from_number_from_monday) {
+        let mut checkbox = Checkbox::new();
+        checkbox.set_checked(weekdays.contains(&weekday));
+        weekday_row.add_child(checkbox.with_name(weekday.short_name()));
+        weekday_row.add_child(TextView::new(format!("{} ", weekday.short_name())));
+    }
+
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(kind_select.with_name("schedule_kind"))
+                .child(TextView::new("Weekdays:"))
+                .child(weekday_row)
+                .child(TextView::new("N:"))
+                .child(
+                    EditView::new()
+                        .content(count)
+                        .max_content_width(3)
+                        .with_name("schedule_count")
+                        .fixed_width(4),
+                ),
+        )
+        .title("Set schedule")
+        .button("Ok", |s| {
+            let schedule = match parse_schedule(s) {
+                Ok(result) => result,
+                Err(error) => {
+                    s.add_layer(Dialog::info(format!("Invalid schedule: {}", error)));
+                    return;
+                }
+            };
+
+            let app_data = s.user_data::<AppData>().unwrap();
+            let mut habit = app_data.current_habit().clone();
+            habit.schedule = schedule;
+            if !app::apply_edit(s, Edit::ReplaceHabit(habit.clone())) {
+                return;
+            }
+
+            s.pop_layer();
+            write_habit_stats(s, &habit);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
 fn back(s: &mut Cursive) {
     let app_data = s.user_data::<AppData>().unwrap();
     app_data.selected_habit = None;
@@ -0,0 +1,181 @@
+use std::fs;
+
+use cursive::traits::*;
+use cursive::views::{Dialog, LinearLayout, TextView};
+use cursive::Cursive;
+
+use crate::app;
+use crate::app::AppData;
+use crate::{backups, AvalancheError, UserData};
+
+/* Shown instead of the habits page when the data file can't be read. The
+ * file is only ever moved aside, never written over, so nothing in it is
+ * lost. That's only offered when its contents are broken: a file from a
+ * newer version or one that can't be read at all may be fine.
+ */
+pub fn draw(s: &mut Cursive, error: &AvalancheError) {
+    if !matches!(
+        error,
+        AvalancheError::Parse { .. } | AvalancheError::Invalid { .. }
+    ) {
+        let filename = s.user_data::<AppData>().unwrap().data_file_name.clone();
+        s.add_layer(
+            Dialog::text(format!(
+                "Could not open the data file {}\n\n{}",
+                filename, error
+            ))
+            .title("Data file can't be opened")
+            .button("Quit", Cursive::quit)
+            .max_width(80),
+        );
+        return;
+    }
+
+    let app_data = s.user_data::<AppData>().unwrap();
+    let filename = app_data.data_file_name.clone();
+    let read_only = app_data.read_only;
+    let latest_backup = backups::find_backups(&filename)
+        .ok()
+        .and_then(|backups| backups.into_iter().next());
+
+    let location = match error {
+        AvalancheError::Parse { line, column, .. } => Some((*line, *column)),
+        _ => None,
+    };
+
+    let mut text = format!("Could not open the data file {}\n\n{}\n\n", filename, error);
+    match &latest_backup {
+        Some(backup) => text += &format!("The most recent backup is {}", backup),
+        None => text += "There are no backups of the data file.",
+    }
+
+    let mut dialog = Dialog::around(TextView::new(text)).title("Data file unreadable");
+    // Moving the file aside is left to the instance that has it open
+    if !read_only {
+        if let Some(backup) = latest_backup {
+            dialog = dialog.button("Open latest backup", move |s| open_backup(s, &backup));
+        }
+    }
+    dialog = dialog.button("View file", move |s| view_file(s, location));
+    if !read_only {
+        dialog = dialog.button("Start fresh", start_fresh);
+    }
+    s.add_layer(dialog.button("Quit", Cursive::quit).max_width(80));
+}
+
+// Moves the broken file aside and continues to the habits page with data
+fn replace_data(s: &mut Cursive, user_data: UserData, unsaved_changes: bool, message: String) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let moved_to = match backups::move_aside(&app_data.data_file_name) {
+        Ok(path) => path,
+        Err(error) => {
+            s.add_layer(Dialog::info(format!(
+                "Failed to move the data file aside:\n{}",
+                error
+            )));
+            return;
+        }
+    };
+    // Edits in the journal were made to the unreadable file, so they go
+    // with it
+    if fs::exists(&app_data.journal.path).unwrap_or(false) {
+        backups::move_aside(&app_data.journal.path).unwrap_or_default();
+    }
+    app_data.user_data = user_data;
+    app_data.unsaved_changes = unsaved_changes;
+
+    s.pop_layer();
+    app::habits_page::draw(s);
+    s.add_layer(Dialog::info(format!(
+        "{}\n\nThe unreadable file was moved to {}",
+        message, moved_to
+    )));
+}
+
+fn open_backup(s: &mut Cursive, backup: &str) {
+    match UserData::read_from_file(backup) {
+        // The restored data only ends up in the data file once it's saved
+        Ok(user_data) => replace_data(
+            s,
+            user_data,
+            true,
+            format!("Opened the backup {}. Save to keep it.", backup),
+        ),
+        Err(error) => s.add_layer(Dialog::info(format!(
+            "Failed to open the backup {}:\n{}",
+            backup, error
+        ))),
+    }
+}
+
+fn start_fresh(s: &mut Cursive) {
+    s.add_layer(
+        Dialog::text("Start with no habits? The unreadable file will be kept next to it.")
+            .title("Start fresh")
+            .button("Yes", |s| {
+                s.pop_layer();
+                replace_data(
+                    s,
+                    UserData::default(),
+                    false,
+                    String::from("Started with no habits."),
+                );
+            })
+            .button("No", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Read only view of the file with the line that failed to parse marked
+fn view_file(s: &mut Cursive, location: Option<(usize, usize)>) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let contents = match fs::read(&app_data.data_file_name) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        Err(error) => {
+            s.add_layer(Dialog::info(format!("Failed to read the file:\n{}", error)));
+            return;
+        }
+    };
+
+    let mut layout = LinearLayout::vertical();
+    if let Some((error_line, column)) = location {
+        // Saved files are a single long line, so show the part around the
+        // error rather than pointing into the wrapped text below
+        let line: Vec<char> = contents
+            .lines()
+            .nth(error_line.saturating_sub(1))
+            .unwrap_or("")
+            .chars()
+            .collect();
+        let error_index = column.saturating_sub(1).min(line.len());
+        let first = error_index.saturating_sub(30);
+        let last = (error_index + 30).min(line.len());
+        let excerpt: String = line[first..last].iter().collect();
+        layout.add_child(TextView::new(format!(
+            "Line {}, column {}:\n{}\n{}^\n",
+            error_line,
+            column,
+            excerpt,
+            " ".repeat(error_index - first)
+        )));
+    }
+
+    let mut text = String::new();
+    for (index, line) in contents.lines().enumerate() {
+        let marker = match location {
+            Some((error_line, _)) if error_line == index + 1 => ">>",
+            _ => "  ",
+        };
+        text += &format!("{} {:>4} | {}\n", marker, index + 1, line);
+    }
+    layout.add_child(TextView::new(text).scrollable().full_screen());
+
+    s.add_layer(
+        Dialog::around(layout)
+            .title("Data file (read only)")
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use cursive::traits::*;
+use cursive::views::{Dialog, SelectView};
+use cursive::Cursive;
+
+use crate::app;
+use crate::app::AppData;
+use crate::{backups, Edit, UserData};
+
+/* Lists the backups of the data file. Restoring only replaces the data in
+ * memory, it's written to the data file on the next save like any other
+ * change.
+ */
+pub fn draw(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let backups = match backups::find_backups(&app_data.data_file_name) {
+        Ok(backups) => backups,
+        Err(error) => {
+            s.add_layer(Dialog::info(format!("Failed to list backups:\n{}", error)));
+            return;
+        }
+    };
+    if backups.is_empty() {
+        s.add_layer(Dialog::info("There are no backups yet"));
+        return;
+    }
+
+    let mut select =
+        SelectView::<String>::new().on_submit(|s, path: &String| confirm_restore(s, path));
+    for path in backups {
+        let info = backups::backup_info(&path);
+        let name = Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(path.clone());
+        let counts = match (info.habit_count, info.record_count) {
+            (Some(habits), Some(records)) => format!("{} habits, {} records", habits, records),
+            _ => String::from("unreadable"),
+        };
+        select.add_item(format!("{} | {}", name, counts), path);
+    }
+
+    s.add_layer(
+        Dialog::around(select.scrollable())
+            .title("Restore backup")
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn confirm_restore(s: &mut Cursive, path: &str) {
+    let user_data = match UserData::read_from_file(path) {
+        Ok(user_data) => user_data,
+        Err(error) => {
+            s.add_layer(Dialog::info(format!(
+                "Failed to read the backup:\n{}",
+                error
+            )));
+            return;
+        }
+    };
+
+    let unsaved_changes = s.user_data::<AppData>().unwrap().unsaved_changes;
+    let mut text = String::from("Replace the current habits with this backup?");
+    if unsaved_changes {
+        text += "\nUnsaved changes will be lost.";
+    }
+    s.add_layer(
+        Dialog::text(text)
+            .title("Restore backup")
+            .button("Yes", move |s| {
+                if !app::apply_edit(s, Edit::ReplaceAll(user_data.clone())) {
+                    return;
+                }
+                s.pop_layer();
+                s.pop_layer();
+                app::habits_page::fill_habit_select(s);
+            })
+            .button("No", |s| {
+                s.pop_layer();
+            }),
+    );
+}
@@ -0,0 +1,70 @@
+use cursive::traits::*;
+use cursive::views::{Dialog, NamedView, TextView};
+use cursive::Cursive;
+
+use crate::app;
+use crate::app::AppData;
+use crate::{Edit, HabitKind, SystemClock};
+use AVALANCHE::timer::{self, Timer};
+
+// Shows the running timer on the records page. Updated every second by
+// tick.
+pub fn view() -> NamedView<TextView> {
+    TextView::new("").with_name("timer_view")
+}
+
+// Starts a timer for the open habit, or stops the running one
+pub fn toggle(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let habit = app_data.current_habit().clone();
+    if habit.kind != HabitKind::Timed {
+        s.add_layer(Dialog::info("Only timed habits have a timer"));
+        return;
+    }
+
+    match &app_data.user_data.timer {
+        None if app_data.pomodoro.is_some() => {
+            s.add_layer(Dialog::info("A pomodoro is running. Stop it first."));
+            return;
+        }
+        None => {
+            let timer = Timer::start(habit.id, &SystemClock);
+            if !app::apply_edit(s, Edit::SetTimer(Some(timer))) {
+                return;
+            }
+        }
+        Some(timer) if timer.habit_id == habit.id => {
+            let (edit, _) = app_data.user_data.stop_timer(&SystemClock).unwrap();
+            if !app::apply_edit(s, edit) {
+                return;
+            }
+            app::records_page::redraw(s);
+        }
+        Some(timer) => {
+            let name = app_data
+                .user_data
+                .find_habit(timer.habit_id)
+                .map_or(String::from("another habit"), |habit| habit.name.clone());
+            s.add_layer(Dialog::info(format!(
+                "The timer for {} is running. Stop it first.",
+                name
+            )));
+            return;
+        }
+    }
+    app::habits_page::fill_habit_select(s);
+    tick(s);
+}
+
+pub fn tick(s: &mut Cursive) {
+    let app_data = s.user_data::<AppData>().unwrap();
+    let text = match (&app_data.user_data.timer, app_data.selected_habit) {
+        (Some(timer), Some(habit_id)) if timer.habit_id == habit_id => format!(
+            "Timer running: {} since {} (press t to stop)",
+            timer::format_seconds(timer.elapsed_seconds(&SystemClock)),
+            timer.start
+        ),
+        _ => String::new(),
+    };
+    s.call_on_name("timer_view", |view: &mut TextView| view.set_content(text));
+}
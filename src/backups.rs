@@ -0,0 +1,120 @@
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::{AvalancheError, UserData};
+
+/* Backups sit next to the data file and are named <data file>.<tag>.bak.
+ * The tag is the version for copies kept before a migration, and a
+ * timestamp for the ones made on every save.
+ */
+
+pub const DEFAULT_BACKUP_COUNT: usize = 5;
+
+pub struct BackupInfo {
+    pub path: String,
+    // None if the backup can't be read
+    pub habit_count: Option<usize>,
+    pub record_count: Option<usize>,
+}
+
+// Paths of the backups of filename, newest first
+pub fn find_backups(filename: &str) -> Result<Vec<String>, AvalancheError> {
+    let path = Path::new(filename);
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let prefix = match path.file_name() {
+        Some(name) => format!("{}.", name.to_string_lossy()),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut backups: Vec<(SystemTime, String)> = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with(&prefix) || !name.ends_with(".bak") {
+            continue;
+        }
+        let modified = entry.metadata()?.modified()?;
+        backups.push((modified, entry.path().to_string_lossy().to_string()));
+    }
+    backups.sort_by(|a, b| b.cmp(a));
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+/* Renames a file that can't be read so a new one can take its place.
+ * Returns where it was moved to.
+ */
+pub fn move_aside(filename: &str) -> Result<String, AvalancheError> {
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let mut destination = format!("{}.corrupt-{}", filename, timestamp);
+    let mut count = 1;
+    while fs::exists(&destination)? {
+        destination = format!("{}.corrupt-{}-{}", filename, timestamp, count);
+        count += 1;
+    }
+    fs::rename(filename, &destination)?;
+    Ok(destination)
+}
+
+/* Copies the data file to a new timestamped backup, then removes the
+ * oldest ones so at most keep are left. Does nothing if there's no data
+ * file yet.
+ */
+pub fn rotate(filename: &str, keep: usize) -> Result<(), AvalancheError> {
+    if keep > 0 && fs::exists(filename)? {
+        let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        let mut destination = format!("{}.{}.bak", filename, timestamp);
+        let mut count = 1;
+        while fs::exists(&destination)? {
+            destination = format!("{}.{}-{}.bak", filename, timestamp, count);
+            count += 1;
+        }
+        fs::copy(filename, &destination)?;
+    }
+
+    let timestamped = find_backups(filename)?
+        .into_iter()
+        .filter(|path| is_timestamped(filename, path));
+    for old_backup in timestamped.skip(keep) {
+        fs::remove_file(old_backup)?;
+    }
+    Ok(())
+}
+
+// Tags made by rotate look like 20250101-170000, maybe followed by -1
+fn is_timestamped(filename: &str, path: &str) -> bool {
+    let name = |path: &str| {
+        Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let tag = name(path)
+        .strip_prefix(&format!("{}.", name(filename)))
+        .and_then(|rest| rest.strip_suffix(".bak"))
+        .map(String::from)
+        .unwrap_or_default();
+    let parts: Vec<&str> = tag.split('-').collect();
+    let digits = |part: &str, length: usize| {
+        part.len() == length && part.chars().all(|c| c.is_ascii_digit())
+    };
+    match parts.as_slice() {
+        [date, time] => digits(date, 8) && digits(time, 6),
+        [date, time, count] => {
+            digits(date, 8) && digits(time, 6) && count.chars().all(|c| c.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+pub fn backup_info(path: &str) -> BackupInfo {
+    let data = UserData::read_from_file(path).ok();
+    BackupInfo {
+        path: String::from(path),
+        habit_count: data.as_ref().map(|data| data.habits.len()),
+        record_count: data.as_ref().map(UserData::record_count),
+    }
+}
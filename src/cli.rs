@@ -0,0 +1,844 @@
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::str::FromStr;
+use AVALANCHE::config::{self, Config, Overrides};
+use AVALANCHE::interchange::json::Json;
+use AVALANCHE::lock::DataLock;
+use AVALANCHE::{
+    backups, new_id, AvalancheError, Clock, Date, Edit, Habit, HabitKind, Record, SystemClock,
+    Time, Timer, UserData, Weekday,
+};
+
+/* Commands for scripts, hooks and cron jobs. They share the data file with
+ * the TUI, and only commands that change it take the lock. Output is text,
+ * or JSON with --json, and errors go to stderr with an exit code from
+ * below.
+ */
+pub const EXIT_ERROR: i32 = 1;
+// Bad arguments. clap uses this too.
+pub const EXIT_USAGE: i32 = 2;
+pub const EXIT_NOT_FOUND: i32 = 3;
+// The data file is open in the TUI
+pub const EXIT_LOCKED: i32 = 4;
+
+#[derive(Parser)]
+#[command(
+    name = "avalanche",
+    version,
+    about = "A habit tracker. Opens the TUI when run without a command."
+)]
+pub struct Cli {
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Config file to read instead of avalanche/config.ron in the config
+    /// directory [env: AVALANCHE_CONFIG]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<String>,
+    /// Use the data file of a profile from the config
+    /// [env: AVALANCHE_PROFILE]
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Data file to use instead of the configured one. Not with --profile.
+    /// [env: AVALANCHE_DATA_FILE]
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "profile")]
+    pub file: Option<String>,
+    #[command(flatten)]
+    pub overrides: OverrideArgs,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Looks up environment variables, so tests don't have to set real ones
+pub type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+impl Cli {
+    // Parses the command line, with the environment of the process
+    pub fn parse_args() -> Cli {
+        Cli::try_parse_with(std::env::args_os(), &|name| std::env::var(name).ok())
+            .unwrap_or_else(|error| error.exit())
+    }
+
+    /* Settings left off the command line are read from the environment
+     * through env. A data file and a profile given on the command line
+     * don't mix, and one given there wins over the other in the
+     * environment.
+     */
+    pub fn try_parse_with<I, T>(args: I, env: Env) -> Result<Cli, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut cli = Cli::try_parse_from(args)?;
+        if cli.file.is_none() && cli.profile.is_none() {
+            from_env(&mut cli.file, "AVALANCHE_DATA_FILE", env, text)?;
+            from_env(&mut cli.profile, "AVALANCHE_PROFILE", env, text)?;
+            if cli.file.is_some() && cli.profile.is_some() {
+                return Err(Cli::command().error(
+                    ErrorKind::ArgumentConflict,
+                    "AVALANCHE_DATA_FILE and AVALANCHE_PROFILE can't both be set",
+                ));
+            }
+        }
+        from_env(&mut cli.config, "AVALANCHE_CONFIG", env, text)?;
+        cli.overrides.read_env(env)?;
+        Ok(cli)
+    }
+}
+
+/* Settings that override the config file. Each can also be set in the
+ * environment, which the command line wins over.
+ */
+#[derive(Clone, Default, Args)]
+pub struct OverrideArgs {
+    /// strftime-like format for dates, like %d/%m/%Y
+    /// [env: AVALANCHE_DATE_FORMAT]
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub date_format: Option<String>,
+    /// strftime-like format for times, like %-I:%M %p
+    /// [env: AVALANCHE_TIME_FORMAT]
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub time_format: Option<String>,
+    /// First day of the week, for weekly goals and schedules
+    /// [env: AVALANCHE_WEEK_START]
+    #[arg(long, global = true, value_name = "DAY", value_parser = config::parse_weekday)]
+    pub week_start: Option<Weekday>,
+    /// Kind of new habits: timed, quit, check-in, or quantity:UNIT
+    /// [env: AVALANCHE_DEFAULT_HABIT_KIND]
+    #[arg(
+        long,
+        global = true,
+        value_name = "KIND",
+        value_parser = config::parse_habit_kind
+    )]
+    pub default_habit_kind: Option<HabitKind>,
+    /// Save the TUI's changes every SECS seconds, or never at 0
+    /// [env: AVALANCHE_AUTOSAVE_SECS]
+    #[arg(long, global = true, value_name = "SECS")]
+    pub autosave_secs: Option<u64>,
+    /// How many timestamped backups of the data file to keep
+    /// [env: AVALANCHE_BACKUP_COUNT]
+    #[arg(long, global = true, value_name = "COUNT")]
+    pub backup_count: Option<usize>,
+    /// Length of pomodoro work intervals
+    /// [env: AVALANCHE_POMODORO_WORK_MINUTES]
+    #[arg(long, global = true, value_name = "MINUTES")]
+    pub pomodoro_work_minutes: Option<u32>,
+    /// Length of pomodoro breaks [env: AVALANCHE_POMODORO_BREAK_MINUTES]
+    #[arg(long, global = true, value_name = "MINUTES")]
+    pub pomodoro_break_minutes: Option<u32>,
+    /// Shell command run when a pomodoro interval ends
+    /// [env: AVALANCHE_POMODORO_HOOK]
+    #[arg(long, global = true, value_name = "COMMAND")]
+    pub pomodoro_hook: Option<String>,
+}
+
+impl OverrideArgs {
+    fn read_env(&mut self, env: Env) -> Result<(), clap::Error> {
+        from_env(&mut self.date_format, "AVALANCHE_DATE_FORMAT", env, text)?;
+        from_env(&mut self.time_format, "AVALANCHE_TIME_FORMAT", env, text)?;
+        from_env(
+            &mut self.week_start,
+            "AVALANCHE_WEEK_START",
+            env,
+            config::parse_weekday,
+        )?;
+        from_env(
+            &mut self.default_habit_kind,
+            "AVALANCHE_DEFAULT_HABIT_KIND",
+            env,
+            config::parse_habit_kind,
+        )?;
+        from_env(
+            &mut self.autosave_secs,
+            "AVALANCHE_AUTOSAVE_SECS",
+            env,
+            number,
+        )?;
+        from_env(
+            &mut self.backup_count,
+            "AVALANCHE_BACKUP_COUNT",
+            env,
+            number,
+        )?;
+        from_env(
+            &mut self.pomodoro_work_minutes,
+            "AVALANCHE_POMODORO_WORK_MINUTES",
+            env,
+            number,
+        )?;
+        from_env(
+            &mut self.pomodoro_break_minutes,
+            "AVALANCHE_POMODORO_BREAK_MINUTES",
+            env,
+            number,
+        )?;
+        from_env(
+            &mut self.pomodoro_hook,
+            "AVALANCHE_POMODORO_HOOK",
+            env,
+            text,
+        )
+    }
+}
+
+impl From<OverrideArgs> for Overrides {
+    fn from(args: OverrideArgs) -> Self {
+        Overrides {
+            date_format: args.date_format,
+            time_format: args.time_format,
+            week_start: args.week_start,
+            default_habit_kind: args.default_habit_kind,
+            autosave_secs: args.autosave_secs,
+            backup_count: args.backup_count,
+            pomodoro_work_minutes: args.pomodoro_work_minutes,
+            pomodoro_break_minutes: args.pomodoro_break_minutes,
+            pomodoro_hook: args.pomodoro_hook,
+        }
+    }
+}
+
+// Fills in a setting left off the command line from the environment
+fn from_env<T>(
+    setting: &mut Option<T>,
+    name: &str,
+    env: Env,
+    parse: fn(&str) -> Result<T, String>,
+) -> Result<(), clap::Error> {
+    if setting.is_some() {
+        return Ok(());
+    }
+    if let Some(value) = env(name) {
+        let value = parse(&value).map_err(|error| {
+            Cli::command().error(ErrorKind::InvalidValue, format!("{}: {}", name, error))
+        })?;
+        *setting = Some(value);
+    }
+    Ok(())
+}
+
+fn text(value: &str) -> Result<String, String> {
+    Ok(String::from(value))
+}
+
+fn number<T: FromStr>(value: &str) -> Result<T, String>
+where
+    T::Err: Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|error: T::Err| error.to_string())
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Add, list, remove or rename habits
+    Habit {
+        #[command(subcommand)]
+        command: HabitCommand,
+    },
+    /// Add a record to a habit
+    Log {
+        habit: String,
+        /// Defaults to today, as YYYY-MM-DD
+        #[arg(long)]
+        date: Option<Date>,
+        /// As HH:MM
+        #[arg(long)]
+        start: Option<Time>,
+        /// As HH:MM. Times before the start are on the next day.
+        #[arg(long, requires = "start")]
+        end: Option<Time>,
+        #[arg(long, default_value = "")]
+        note: String,
+        /// For habits that count an amount
+        #[arg(long)]
+        amount: Option<f64>,
+    },
+    /// Start timing a session of a habit
+    Start {
+        habit: String,
+        #[arg(long, default_value = "")]
+        note: String,
+    },
+    /// Stop the timer and add its record
+    Stop,
+    /// List a habit's records
+    Records { habit: String },
+    /// Show a habit's streaks, goals and totals
+    Stats { habit: String },
+}
+
+#[derive(Subcommand)]
+pub enum HabitCommand {
+    /// Add a habit
+    Add {
+        name: String,
+        /// Defaults to default_habit_kind from the config
+        #[arg(long, value_enum)]
+        kind: Option<Kind>,
+        /// Unit of quantity habits, like pages or glasses
+        #[arg(long, required_if_eq("kind", "quantity"))]
+        unit: Option<String>,
+    },
+    /// List the habits
+    List,
+    /// Remove a habit and its records
+    Rm { habit: String },
+    /// Rename a habit
+    Rename { habit: String, new_name: String },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Kind {
+    Timed,
+    Quit,
+    Quantity,
+    CheckIn,
+}
+
+enum CliError {
+    Usage(String),
+    NotFound(String),
+    Data(AvalancheError),
+}
+
+impl From<AvalancheError> for CliError {
+    fn from(error: AvalancheError) -> Self {
+        CliError::Data(error)
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(error: serde_json::Error) -> Self {
+        CliError::Data(error.into())
+    }
+}
+
+// Runs the command and returns the exit code
+pub fn run(cli: Cli, filename: &str, config: &Config) -> i32 {
+    let Some(command) = cli.command else {
+        return 0;
+    };
+    let backup_count = config.backup_count;
+    let result = match command {
+        Command::Habit { command } => habit(command, filename, config, cli.json),
+        Command::Log {
+            habit,
+            date,
+            start,
+            end,
+            note,
+            amount,
+        } => {
+            let record = Record {
+                id: new_id(),
+                note,
+                date: date.unwrap_or_else(|| SystemClock.today()),
+                start_time: start,
+                end_time: end,
+                end_date: None,
+                amount,
+                pomodoro: false,
+            };
+            log(&habit, record, filename, backup_count, cli.json)
+        }
+        Command::Start { habit, note } => start(&habit, note, filename, backup_count, cli.json),
+        Command::Stop => stop(filename, backup_count, cli.json),
+        Command::Records { habit } => records(&habit, filename, cli.json),
+        Command::Stats { habit } => stats(&habit, filename, config, cli.json),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(error) => {
+            let (message, code) = match error {
+                CliError::Usage(message) => (message, EXIT_USAGE),
+                CliError::NotFound(message) => (message, EXIT_NOT_FOUND),
+                CliError::Data(error @ AvalancheError::Locked { .. }) => {
+                    (error.to_string(), EXIT_LOCKED)
+                }
+                CliError::Data(error) => (error.to_string(), EXIT_ERROR),
+            };
+            eprintln!("avalanche: {}", message);
+            code
+        }
+    }
+}
+
+fn habit(
+    command: HabitCommand,
+    filename: &str,
+    config: &Config,
+    json: bool,
+) -> Result<(), CliError> {
+    if let HabitCommand::List = command {
+        let data = UserData::try_from_file(filename)?;
+        if json {
+            let habits: Vec<_> = data
+                .habits
+                .iter()
+                .map(|habit| {
+                    json!({
+                        "id": habit.id.to_string(),
+                        "name": habit.name,
+                        "kind": kind_name(&habit.kind),
+                        "records": habit.records.len(),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&habits)?);
+        } else {
+            for habit in &data.habits {
+                println!(
+                    "{} ({}, {} records)",
+                    habit.name,
+                    kind_name(&habit.kind),
+                    habit.records.len()
+                );
+            }
+        }
+        return Ok(());
+    }
+
+    let mut message = String::new();
+    let (mut id, mut name) = (0, String::new());
+    change(filename, config.backup_count, |data| match &command {
+        HabitCommand::Add {
+            name: new_name,
+            kind,
+            unit,
+        } => {
+            name = check_name(data, new_name, None)?;
+            let kind = match (kind, &config.default_habit_kind) {
+                (None, HabitKind::Quantity { unit: default_unit }) => HabitKind::Quantity {
+                    unit: unit.clone().unwrap_or(default_unit.clone()),
+                },
+                (None, default) => default.clone(),
+                (Some(Kind::Timed), _) => HabitKind::Timed,
+                (Some(Kind::Quit), _) => HabitKind::Quit,
+                (Some(Kind::Quantity), _) => HabitKind::Quantity {
+                    unit: unit.clone().unwrap_or_default(),
+                },
+                (Some(Kind::CheckIn), _) => HabitKind::CheckIn,
+            };
+            if matches!(&kind, HabitKind::Quantity { unit } if unit.trim().is_empty()) {
+                return Err(CliError::Usage(String::from(
+                    "Quantity habits need a --unit",
+                )));
+            }
+            id = new_id();
+            message = format!("Added {}", name);
+            let habit = Habit {
+                id,
+                name: name.clone(),
+                kind,
+                start_date: Some(SystemClock.today()),
+                ..Default::default()
+            };
+            Ok(Edit::InsertHabit {
+                index: data.habits.len(),
+                habit,
+            })
+        }
+        HabitCommand::Rm { habit } => {
+            let habit = find_habit(data, habit)?;
+            (id, name) = (habit.id, habit.name.clone());
+            message = format!("Removed {}", name);
+            Ok(Edit::RemoveHabit { id })
+        }
+        HabitCommand::Rename { habit, new_name } => {
+            let habit = find_habit(data, habit)?;
+            (id, name) = (habit.id, check_name(data, new_name, Some(habit.id))?);
+            message = format!("Renamed {} to {}", habit.name, name);
+            Ok(Edit::ReplaceHabit(Habit {
+                name: name.clone(),
+                ..habit.clone()
+            }))
+        }
+        HabitCommand::List => unreachable!(),
+    })?;
+
+    if json {
+        println!("{}", json!({ "id": id.to_string(), "name": name }));
+    } else {
+        println!("{}", message);
+    }
+    Ok(())
+}
+
+// Names are kept unique, like in the TUI
+fn check_name(data: &UserData, name: &str, habit_id: Option<u64>) -> Result<String, CliError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CliError::Usage(String::from("The name can't be empty")));
+    }
+    if data
+        .find_habit_by_name(name)
+        .is_some_and(|id| Some(id) != habit_id)
+    {
+        return Err(CliError::Usage(format!("{} already exists", name)));
+    }
+    Ok(String::from(name))
+}
+
+fn log(
+    habit: &str,
+    mut record: Record,
+    filename: &str,
+    backup_count: usize,
+    json: bool,
+) -> Result<(), CliError> {
+    // Like CSV imports, an end earlier in the day than the start is on the
+    // next day
+    if let (Some(start), Some(end)) = (&record.start_time, &record.end_time) {
+        if end.as_minutes() < start.as_minutes() {
+            record.end_date = Some(record.date.add_days(1));
+        }
+    }
+    if record
+        .amount
+        .is_some_and(|amount| !amount.is_finite() || amount < 0.0)
+    {
+        return Err(CliError::Usage(String::from(
+            "The amount can't be negative",
+        )));
+    }
+    record
+        .validate()
+        .map_err(|error| CliError::Usage(error.to_string()))?;
+
+    let mut name = String::new();
+    change(filename, backup_count, |data| {
+        let habit = find_habit(data, habit)?;
+        check_fields(habit, &record)?;
+        name = habit.name.clone();
+        Ok(Edit::InsertRecord {
+            habit_id: habit.id,
+            index: habit.records.len(),
+            record: record.clone(),
+        })
+    })?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&record.to_value()?)?);
+    } else {
+        println!("Logged {} for {}", describe_record(&record), name);
+    }
+    Ok(())
+}
+
+// Records only get the fields the TUI asks for with the habit's kind
+fn check_fields(habit: &Habit, record: &Record) -> Result<(), CliError> {
+    let (times, end, amount) = match habit.kind {
+        HabitKind::Timed => (true, true, false),
+        // Relapses happen at a point in time
+        HabitKind::Quit => (true, false, false),
+        HabitKind::Quantity { .. } => (false, false, true),
+        HabitKind::CheckIn => (false, false, false),
+    };
+    let unexpected = [
+        ("--start", !times && record.start_time.is_some()),
+        ("--end", !end && record.end_time.is_some()),
+        ("--amount", !amount && record.amount.is_some()),
+    ]
+    .into_iter()
+    .find(|(_, unexpected)| *unexpected);
+    if let Some((flag, _)) = unexpected {
+        return Err(CliError::Usage(format!(
+            "{} is a {} habit, so it doesn't take {}",
+            habit.name,
+            kind_name(&habit.kind),
+            flag
+        )));
+    }
+    if habit.kind == HabitKind::Timed && record.end_time.is_none() {
+        return Err(CliError::Usage(format!(
+            "{} is timed. Give the times with --start and --end.",
+            habit.name
+        )));
+    }
+    if amount && record.amount.is_none() {
+        return Err(CliError::Usage(format!(
+            "{} counts an amount. Give one with --amount.",
+            habit.name
+        )));
+    }
+    Ok(())
+}
+
+fn start(
+    habit: &str,
+    note: String,
+    filename: &str,
+    backup_count: usize,
+    json: bool,
+) -> Result<(), CliError> {
+    let mut timer = None;
+    change(filename, backup_count, |data| {
+        if let Some(running) = &data.timer {
+            let name = data
+                .find_habit(running.habit_id)
+                .map_or("", |habit| habit.name.as_str());
+            return Err(CliError::Usage(format!(
+                "The timer for {} is already running",
+                name
+            )));
+        }
+        let habit = find_habit(data, habit)?;
+        if habit.kind != HabitKind::Timed {
+            return Err(CliError::Usage(format!(
+                "{} isn't a timed habit",
+                habit.name
+            )));
+        }
+        timer = Some(Timer {
+            note,
+            ..Timer::start(habit.id, &SystemClock)
+        });
+        Ok(Edit::SetTimer(timer.clone()))
+    })?;
+
+    let timer = timer.unwrap();
+    if json {
+        println!(
+            "{}",
+            json!({
+                "habit_id": timer.habit_id.to_string(),
+                "date": timer.date.to_string(),
+                "start": timer.start.to_string(),
+            })
+        );
+    } else {
+        println!("Started the timer at {}", timer.start);
+    }
+    Ok(())
+}
+
+fn stop(filename: &str, backup_count: usize, json: bool) -> Result<(), CliError> {
+    let mut stopped = None;
+    change(filename, backup_count, |data| {
+        let (edit, record) = data
+            .stop_timer(&SystemClock)
+            .ok_or_else(|| CliError::NotFound(String::from("No timer is running")))?;
+        let name = data
+            .timer
+            .as_ref()
+            .and_then(|timer| data.find_habit(timer.habit_id))
+            .map(|habit| habit.name.clone());
+        stopped = Some((name, record));
+        Ok(edit)
+    })?;
+
+    match stopped.unwrap() {
+        (Some(name), Some(record)) if !json => {
+            println!("Logged {} for {}", describe_record(&record), name)
+        }
+        (_, Some(record)) => println!("{}", serde_json::to_string_pretty(&record.to_value()?)?),
+        // The habit was removed while the timer ran
+        (_, None) if json => println!("null"),
+        (_, None) => println!("Stopped the timer"),
+    }
+    Ok(())
+}
+
+fn records(habit: &str, filename: &str, json: bool) -> Result<(), CliError> {
+    let data = UserData::try_from_file(filename)?;
+    let habit = find_habit(&data, habit)?;
+    if json {
+        let records = habit
+            .records
+            .iter()
+            .map(Record::to_value)
+            .collect::<Result<Vec<_>, AvalancheError>>()?;
+        println!("{}", serde_json::to_string_pretty(&records)?);
+    } else {
+        for record in &habit.records {
+            println!("{}", describe_record(record));
+        }
+    }
+    Ok(())
+}
+
+fn stats(habit: &str, filename: &str, config: &Config, json: bool) -> Result<(), CliError> {
+    let data = UserData::try_from_file(filename)?;
+    let habit = find_habit(&data, habit)?;
+    let stats = habit.get_stats_with(&SystemClock, config.week_start);
+    let minutes = |time: &Time| time.as_minutes();
+    let goal = |progress: &Option<AVALANCHE::GoalProgress>| {
+        progress.as_ref().map(|progress| {
+            json!({
+                "target_minutes": minutes(&progress.target),
+                "current_minutes": minutes(&progress.current),
+                "met": progress.met,
+                "missed": progress.missed,
+            })
+        })
+    };
+
+    if json {
+        let mut value = json!({
+            "habit": habit.name,
+            "records": habit.records.len(),
+            "current_streak": stats.current_streak,
+            "streak_unit": habit.schedule.streak_unit(),
+            "streak_at_risk": stats.streak_at_risk,
+            "longest_streak": stats.longest_streak.as_ref().map(|streak| json!({
+                "length": streak.length,
+                "start": streak.start.to_string(),
+                "end": streak.end.to_string(),
+            })),
+            "completion_rate": stats.completion_rate,
+            "total_minutes": minutes(&stats.total_time),
+            "pomodoros": stats.pomodoros,
+            "daily_goal": goal(&stats.daily_goal),
+            "weekly_goal": goal(&stats.weekly_goal),
+            "monthly_goal": goal(&stats.monthly_goal),
+        });
+        if let Some(quit) = &stats.quit {
+            value["quit"] = json!({
+                "days_since_relapse": quit.days_since_relapse,
+                "longest_clean_run": quit.longest_clean_run,
+                "relapses": quit.relapse_count,
+                "relapses_per_week": quit.relapses_per_week,
+            });
+        }
+        if let Some(quantity) = &stats.quantity {
+            value["quantity"] = json!({
+                "unit": quantity.unit,
+                "total": quantity.total,
+                "average_per_record": quantity.average_per_record,
+                "average_per_day": quantity.average_per_day,
+            });
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
+        return Ok(());
+    }
+
+    println!("{}: {}", habit.name, habit.schedule.describe());
+    println!("Records: {}", habit.records.len());
+    if let Some(quit) = &stats.quit {
+        match quit.days_since_relapse {
+            Some(days) => println!("Days since last relapse: {}", days),
+            None => println!("Days since last relapse: no relapses"),
+        }
+        println!("Longest clean run: {} days", quit.longest_clean_run);
+        println!("Relapses: {}", quit.relapse_count);
+        return Ok(());
+    }
+    let unit = habit.schedule.streak_unit();
+    println!(
+        "Current streak: {} {}{}",
+        stats.current_streak,
+        unit,
+        if stats.streak_at_risk {
+            " (not done today yet)"
+        } else {
+            ""
+        }
+    );
+    if let Some(streak) = &stats.longest_streak {
+        println!(
+            "Longest streak: {} {} ({} to {})",
+            streak.length, unit, streak.start, streak.end
+        );
+    }
+    if let Some(rate) = stats.completion_rate {
+        println!("Completion: {:.0}%", rate * 100.0);
+    }
+    match &stats.quantity {
+        Some(quantity) => println!("Total: {} {}", quantity.total, quantity.unit),
+        None if habit.kind == HabitKind::CheckIn => (),
+        None => println!(
+            "Total time: {}h {:02}m | Pomodoros: {}",
+            stats.total_time.hours, stats.total_time.minutes, stats.pomodoros
+        ),
+    }
+    for (label, progress) in [
+        ("Daily", &stats.daily_goal),
+        ("Weekly", &stats.weekly_goal),
+        ("Monthly", &stats.monthly_goal),
+    ] {
+        if let Some(progress) = progress {
+            println!(
+                "{} goal: {} of {} minutes | Met {} times, missed {} times",
+                label,
+                minutes(&progress.current),
+                minutes(&progress.target),
+                progress.met,
+                progress.missed
+            );
+        }
+    }
+    Ok(())
+}
+
+/* Makes the edit returned by make_edit to the data file. The file is read
+ * after taking the lock, so the edit is made against what's on disk.
+ */
+fn change<F>(filename: &str, backup_count: usize, make_edit: F) -> Result<(), CliError>
+where
+    F: FnOnce(&UserData) -> Result<Edit, CliError>,
+{
+    let _lock = DataLock::acquire(filename)?;
+    let mut data = UserData::try_from_file(filename)?;
+    let edit = make_edit(&data)?;
+    // The habit or record the edit is for has gone
+    edit.apply(&mut data).ok_or_else(|| {
+        CliError::NotFound(String::from(
+            "The data file doesn't have what was to be changed",
+        ))
+    })?;
+    backups::rotate(filename, backup_count)?;
+    data.write_to_file(filename)?;
+    Ok(())
+}
+
+// By name, or by ID as printed with --json
+fn find_habit<'a>(data: &'a UserData, habit: &str) -> Result<&'a Habit, CliError> {
+    data.find_habit_by_name(habit)
+        .or_else(|| {
+            habit
+                .parse()
+                .ok()
+                .filter(|id| data.find_habit(*id).is_some())
+        })
+        .and_then(|id| data.find_habit(id))
+        .ok_or_else(|| CliError::NotFound(format!("No habit called {}", habit)))
+}
+
+// Spelled the way --kind takes it
+fn kind_name(kind: &HabitKind) -> String {
+    let kind = match kind {
+        HabitKind::Timed => Kind::Timed,
+        HabitKind::Quit => Kind::Quit,
+        HabitKind::Quantity { .. } => Kind::Quantity,
+        HabitKind::CheckIn => Kind::CheckIn,
+    };
+    kind.to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn describe_record(record: &Record) -> String {
+    let mut description = record.date.to_string();
+    if let Some(start) = &record.start_time {
+        description += &format!(" {}", start);
+        if let Some(end) = &record.end_time {
+            description += &format!("-{}", end);
+            if let Ok(length) = record.length() {
+                description += &format!(" ({}h {:02}m)", length.hours, length.minutes);
+            }
+        }
+    }
+    if let Some(amount) = record.amount {
+        description += &format!(" {}", amount);
+    }
+    if !record.note.is_empty() {
+        description += &format!(" {}", record.note);
+    }
+    description
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::fs;
+
+use clap::error::ErrorKind;
+use clap::Parser;
+use AVALANCHE::config::{Config, Overrides};
+use AVALANCHE::lock::DataLock;
+use AVALANCHE::{HabitKind, UserData, Weekday};
+
+use crate::cli::{self, Cli};
+
+fn run(filename: &str, args: &[&str]) -> i32 {
+    let cli = Cli::parse_from(["avalanche"].iter().chain(args));
+    let config = Config {
+        backup_count: 0,
+        ..Default::default()
+    };
+    cli::run(cli, filename, &config)
+}
+
+fn records(filename: &str, habit: &str) -> usize {
+    let data = UserData::try_from_file(filename).unwrap();
+    let id = data.find_habit_by_name(habit).unwrap();
+    data.find_habit(id).unwrap().records.len()
+}
+
+#[test]
+fn log_fields_test() {
+    let file = "test_cli_log.ron";
+    fs::remove_file(file).unwrap_or(());
+    assert_eq!(run(file, &["habit", "add", "Run", "--kind", "timed"]), 0);
+    assert_eq!(
+        run(
+            file,
+            &["habit", "add", "Read", "--kind", "quantity", "--unit", "pages"]
+        ),
+        0
+    );
+    assert_eq!(run(file, &["habit", "add", "Smoke", "--kind", "quit"]), 0);
+    assert_eq!(
+        run(file, &["habit", "add", "Floss", "--kind", "check-in"]),
+        0
+    );
+
+    // Fields that don't belong to the kind
+    for args in [
+        &["log", "Run", "--amount", "5"][..],
+        &["log", "Run", "--start", "10:00"],
+        &[
+            "log", "Run", "--start", "10:00", "--end", "11:00", "--amount", "5",
+        ],
+        &["log", "Read", "--amount", "3", "--start", "10:00"],
+        &["log", "Read"],
+        &["log", "Smoke", "--start", "10:00", "--end", "11:00"],
+        &["log", "Smoke", "--amount", "1"],
+        &["log", "Floss", "--start", "10:00"],
+        &["log", "Floss", "--amount", "1"],
+    ] {
+        assert_eq!(run(file, args), cli::EXIT_USAGE, "{:?}", args);
+    }
+    assert_eq!(run(file, &["log", "Read", "--amount=-1"]), cli::EXIT_USAGE);
+    for habit in ["Run", "Read", "Smoke", "Floss"] {
+        assert_eq!(records(file, habit), 0);
+    }
+
+    assert_eq!(
+        run(file, &["log", "Run", "--start", "23:00", "--end", "01:00"]),
+        0
+    );
+    assert_eq!(run(file, &["log", "Read", "--amount", "3"]), 0);
+    assert_eq!(run(file, &["log", "Smoke", "--start", "10:00"]), 0);
+    assert_eq!(run(file, &["log", "Smoke"]), 0);
+    assert_eq!(run(file, &["log", "Floss"]), 0);
+    assert_eq!(records(file, "Run"), 1);
+    assert_eq!(records(file, "Read"), 1);
+    assert_eq!(records(file, "Smoke"), 2);
+    assert_eq!(records(file, "Floss"), 1);
+
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn habit_usage_test() {
+    let file = "test_cli_habit.ron";
+    fs::remove_file(file).unwrap_or(());
+    assert_eq!(run(file, &["habit", "add", "Run"]), 0);
+    assert_eq!(run(file, &["habit", "add", " Run "]), cli::EXIT_USAGE);
+    assert_eq!(run(file, &["habit", "add", " "]), cli::EXIT_USAGE);
+    assert_eq!(run(file, &["habit", "add", "Walk"]), 0);
+    assert_eq!(
+        run(file, &["habit", "rename", "Walk", "Run"]),
+        cli::EXIT_USAGE
+    );
+    assert_eq!(run(file, &["start", "Run"]), 0);
+    assert_eq!(run(file, &["start", "Walk"]), cli::EXIT_USAGE);
+    assert_eq!(run(file, &["stop"]), 0);
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn not_found_test() {
+    let file = "test_cli_not_found.ron";
+    fs::remove_file(file).unwrap_or(());
+    assert_eq!(run(file, &["habit", "add", "Run"]), 0);
+    assert_eq!(
+        run(file, &["log", "Walk", "--start", "10:00", "--end", "11:00"]),
+        cli::EXIT_NOT_FOUND
+    );
+    assert_eq!(run(file, &["habit", "rm", "Walk"]), cli::EXIT_NOT_FOUND);
+    assert_eq!(run(file, &["records", "Walk"]), cli::EXIT_NOT_FOUND);
+    assert_eq!(run(file, &["stats", "Walk"]), cli::EXIT_NOT_FOUND);
+    assert_eq!(run(file, &["stop"]), cli::EXIT_NOT_FOUND);
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn locked_test() {
+    let file = "test_cli_locked.ron";
+    fs::remove_file(file).unwrap_or(());
+    assert_eq!(run(file, &["habit", "add", "Run"]), 0);
+    let lock = DataLock::acquire(file).unwrap();
+    assert_eq!(run(file, &["habit", "add", "Walk"]), cli::EXIT_LOCKED);
+    assert_eq!(
+        run(file, &["log", "Run", "--start", "10:00", "--end", "11:00"]),
+        cli::EXIT_LOCKED
+    );
+    // Reading doesn't need the lock
+    assert_eq!(run(file, &["records", "Run"]), 0);
+    drop(lock);
+    assert_eq!(run(file, &["habit", "add", "Walk"]), 0);
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn file_and_profile_test() {
+    let error = parse_with(&["--file", "a.ron", "--profile", "work"], &[])
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+    let error = parse_with(
+        &["stats", "Run", "--profile", "work", "--file", "a.ron"],
+        &[],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+
+    // Either one on the command line wins over the other in the
+    // environment
+    let vars = [("AVALANCHE_DATA_FILE", "env.ron")];
+    let cli = parse_with(&["--profile", "work"], &vars).unwrap();
+    assert_eq!(cli.profile.as_deref(), Some("work"));
+    assert_eq!(cli.file, None);
+    let cli = parse_with(&[], &vars).unwrap();
+    assert_eq!(cli.file.as_deref(), Some("env.ron"));
+    let cli = parse_with(&["--file", "a.ron"], &[("AVALANCHE_PROFILE", "work")]).unwrap();
+    assert_eq!(cli.file.as_deref(), Some("a.ron"));
+    assert_eq!(cli.profile, None);
+
+    let vars = [
+        ("AVALANCHE_DATA_FILE", "env.ron"),
+        ("AVALANCHE_PROFILE", "work"),
+    ];
+    let error = parse_with(&[], &vars).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+}
+
+// Parses args with only the variables in vars set
+fn parse_with(args: &[&str], vars: &[(&str, &str)]) -> Result<Cli, clap::Error> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    Cli::try_parse_with(["avalanche"].iter().chain(args), &|name| {
+        vars.get(name).cloned()
+    })
+}
+
+#[test]
+fn overrides_test() {
+    // Flags win over the environment, which wins over the file
+    let vars = [
+        ("AVALANCHE_DATE_FORMAT", "%m/%d/%Y"),
+        ("AVALANCHE_WEEK_START", "saturday"),
+        ("AVALANCHE_BACKUP_COUNT", "5"),
+        ("AVALANCHE_CONFIG", "config.ron"),
+    ];
+    let cli = parse_with(
+        &[
+            "stats",
+            "Run",
+            "--date-format",
+            "%Y/%m/%d",
+            "--default-habit-kind",
+            "quantity:pages",
+        ],
+        &vars,
+    )
+    .unwrap();
+    assert_eq!(cli.config.as_deref(), Some("config.ron"));
+    let overrides: Overrides = cli.overrides.into();
+    assert_eq!(overrides.date_format.as_deref(), Some("%Y/%m/%d"));
+    assert_eq!(overrides.week_start, Some(Weekday::Saturday));
+    assert_eq!(overrides.backup_count, Some(5));
+    assert_eq!(overrides.time_format, None);
+    assert_eq!(
+        overrides.default_habit_kind,
+        Some(HabitKind::Quantity {
+            unit: String::from("pages")
+        })
+    );
+
+    // Values that can't be used are refused, wherever they're from
+    for (args, vars) in [
+        (&["--week-start", "someday"][..], &[][..]),
+        (&["--default-habit-kind", "timed:x"], &[]),
+        (&[], &[("AVALANCHE_WEEK_START", "someday")]),
+        (&[], &[("AVALANCHE_AUTOSAVE_SECS", "-1")]),
+    ] {
+        let error = parse_with(args, vars).err().unwrap();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::InvalidValue | ErrorKind::ValueValidation
+        ));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::format::StrftimeItems;
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{backups, pomodoro, AvalancheError, Date, HabitKind, Time, ValidationError, Weekday};
+
+/* Settings read from config.ron in the avalanche folder of the config
+ * directory ($XDG_CONFIG_HOME or ~/.config). Anything left out keeps its
+ * default, and a missing file means all defaults. Command-line flags and
+ * environment variables override what's read here.
+ */
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // The data directory's .avalanche if not set. ~ is the home directory.
+    pub data_file: Option<String>,
+    // strftime-like formats, as in chrono::format::strftime
+    pub date_format: String,
+    pub time_format: String,
+    pub week_start: Weekday,
+    // Autosave is off at 0
+    pub autosave_secs: u64,
+    // How many timestamped backups to keep next to the data file
+    pub backup_count: usize,
+    // Selected when adding a habit
+    pub default_habit_kind: HabitKind,
+    pub pomodoro: PomodoroConfig,
+    // Named data files, like separate work and personal habits
+    pub profiles: BTreeMap<String, String>,
+}
+
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PomodoroConfig {
+    pub work_minutes: u32,
+    pub break_minutes: u32,
+    // Shell command run when an interval ends
+    pub hook: Option<String>,
+}
+
+/* Settings given on the command line or in the environment, which win over
+ * the file. None leaves what was read alone.
+ */
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Overrides {
+    pub date_format: Option<String>,
+    pub time_format: Option<String>,
+    pub week_start: Option<Weekday>,
+    pub default_habit_kind: Option<HabitKind>,
+    pub autosave_secs: Option<u64>,
+    pub backup_count: Option<usize>,
+    pub pomodoro_work_minutes: Option<u32>,
+    pub pomodoro_break_minutes: Option<u32>,
+    pub pomodoro_hook: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            data_file: None,
+            date_format: String::from("%Y-%-m-%-d"),
+            time_format: String::from("%H:%M"),
+            week_start: Weekday::Monday,
+            autosave_secs: 0,
+            backup_count: backups::DEFAULT_BACKUP_COUNT,
+            default_habit_kind: HabitKind::Timed,
+            pomodoro: PomodoroConfig::default(),
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        PomodoroConfig {
+            work_minutes: pomodoro::DEFAULT_WORK_MINUTES,
+            break_minutes: pomodoro::DEFAULT_BREAK_MINUTES,
+            hook: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("avalanche").join("config.ron"))
+    }
+
+    pub fn read_from_file(filename: &str) -> Result<Config, AvalancheError> {
+        let config: Config = ron::from_str(&fs::read_to_string(filename)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Reads the config, or the defaults if there isn't one
+    pub fn try_from_file(filename: &str) -> Result<Config, AvalancheError> {
+        if !fs::exists(filename)? {
+            return Ok(Config::default());
+        }
+        Config::read_from_file(filename)
+    }
+
+    // Puts the overrides in place of what was read, and checks the result
+    pub fn apply(&mut self, overrides: &Overrides) -> Result<(), AvalancheError> {
+        let overrides = overrides.clone();
+        if let Some(format) = overrides.date_format {
+            self.date_format = format;
+        }
+        if let Some(format) = overrides.time_format {
+            self.time_format = format;
+        }
+        if let Some(day) = overrides.week_start {
+            self.week_start = day;
+        }
+        if let Some(kind) = overrides.default_habit_kind {
+            self.default_habit_kind = kind;
+        }
+        if let Some(secs) = overrides.autosave_secs {
+            self.autosave_secs = secs;
+        }
+        if let Some(count) = overrides.backup_count {
+            self.backup_count = count;
+        }
+        if let Some(minutes) = overrides.pomodoro_work_minutes {
+            self.pomodoro.work_minutes = minutes;
+        }
+        if let Some(minutes) = overrides.pomodoro_break_minutes {
+            self.pomodoro.break_minutes = minutes;
+        }
+        if let Some(hook) = overrides.pomodoro_hook {
+            self.pomodoro.hook = Some(hook);
+        }
+        self.validate()
+    }
+
+    pub fn validate(&self) -> Result<(), AvalancheError> {
+        let invalid = |location: &str, value: String| AvalancheError::Invalid {
+            location: String::from(location),
+            error: ValidationError::Unreadable(value),
+        };
+        // Formats are checked by using them, as some only fail then, like
+        // hours in a date
+        let (date, time) = (NaiveDate::MIN, NaiveTime::MIN);
+        if formatted(date.format_with_items(StrftimeItems::new(&self.date_format))).is_none() {
+            return Err(invalid("date_format", self.date_format.clone()));
+        }
+        if formatted(time.format_with_items(StrftimeItems::new(&self.time_format))).is_none() {
+            return Err(invalid("time_format", self.time_format.clone()));
+        }
+        for (location, minutes) in [
+            ("pomodoro.work_minutes", self.pomodoro.work_minutes),
+            ("pomodoro.break_minutes", self.pomodoro.break_minutes),
+        ] {
+            if !(1..=1440).contains(&minutes) {
+                return Err(invalid(location, minutes.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // The configured data file, or the default one. None if there's no data
+    // directory to keep it in.
+    pub fn data_file_path(&self) -> Option<String> {
+        match &self.data_file {
+            Some(path) => Some(expand_home(path)),
+            None => {
+                dirs::data_dir().map(|dir| dir.join(".avalanche").to_string_lossy().to_string())
+            }
+        }
+    }
+
+    // The data file of a profile, if there's one by that name
+    pub fn profile_file_path(&self, profile: &str) -> Option<String> {
+        self.profiles.get(profile).map(|path| expand_home(path))
+    }
+
+    pub fn format_date(&self, date: &Date) -> String {
+        NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)
+            .and_then(|date| {
+                formatted(date.format_with_items(StrftimeItems::new(&self.date_format)))
+            })
+            .unwrap_or_else(|| date.to_string())
+    }
+
+    pub fn format_time(&self, time: &Time) -> String {
+        NaiveTime::from_hms_opt(time.hours as u32, time.minutes as u32, 0)
+            .and_then(|time| {
+                formatted(time.format_with_items(StrftimeItems::new(&self.time_format)))
+            })
+            .unwrap_or_else(|| time.to_string())
+    }
+}
+
+// None if the format can't be used for the value
+fn formatted(value: impl fmt::Display) -> Option<String> {
+    let mut out = String::new();
+    write!(out, "{}", value).ok()?;
+    Some(out)
+}
+
+// Weekday names, in any case
+pub fn parse_weekday(value: &str) -> Result<Weekday, String> {
+    Weekday::ALL
+        .into_iter()
+        .find(|day| format!("{:?}", day).eq_ignore_ascii_case(value.trim()))
+        .ok_or_else(|| format!("{} isn't a day of the week", value))
+}
+
+// timed, quit, check-in or quantity:UNIT, as given on the command line
+pub fn parse_habit_kind(value: &str) -> Result<HabitKind, String> {
+    let (kind, unit) = match value.trim().split_once(':') {
+        Some((kind, unit)) => (kind, Some(unit.trim())),
+        None => (value.trim(), None),
+    };
+    match (kind.to_lowercase().as_str(), unit) {
+        ("timed", None) => Ok(HabitKind::Timed),
+        ("quit", None) => Ok(HabitKind::Quit),
+        ("check-in", None) => Ok(HabitKind::CheckIn),
+        ("quantity", unit) => Ok(HabitKind::Quantity {
+            unit: unit.unwrap_or("").to_string(),
+        }),
+        _ => Err(format!(
+            "{} should be timed, quit, check-in or quantity:UNIT",
+            value
+        )),
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => path.to_string(),
+    }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Habit, Record, Timer, UserData};
+
+/* A single change to the user data. Applying one returns the edit that
+ * undoes it, and edits are written to the journal as they're made so they
+ * can be replayed after a crash.
+ *
+ * Inserted habits and records should already have their IDs, so replaying
+ * an edit gives the same result every time. Inserting one whose ID is
+ * already there is skipped, so replaying edits that were saved doesn't add
+ * them twice.
+ */
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub enum Edit {
+    InsertHabit {
+        index: usize,
+        habit: Habit,
+    },
+    RemoveHabit {
+        id: u64,
+    },
+    // Replaces the settings of the habit with the same ID. Its records are
+    // left alone, so edits to settings and records can be merged.
+    ReplaceHabit(Habit),
+    InsertRecord {
+        habit_id: u64,
+        index: usize,
+        record: Record,
+    },
+    RemoveRecord {
+        habit_id: u64,
+        id: u64,
+    },
+    ReplaceRecord {
+        habit_id: u64,
+        record: Record,
+    },
+    // Everything at once, like when a backup is restored
+    ReplaceAll(UserData),
+    SetTimer(Option<Timer>),
+    // Several edits made together, like stopping a timer and adding its
+    // record. Nothing is changed if any of them can't be applied.
+    Batch(Vec<Edit>),
+}
+
+impl Edit {
+    // Returns None without changing anything if what the edit refers to
+    // doesn't exist
+    pub fn apply(self, data: &mut UserData) -> Option<Edit> {
+        match self {
+            Edit::InsertHabit { index, habit } => {
+                let id = habit.id;
+                if data.find_habit(id).is_some() {
+                    return None;
+                }
+                data.habits.insert(index.min(data.habits.len()), habit);
+                Some(Edit::RemoveHabit { id })
+            }
+            Edit::RemoveHabit { id } => {
+                let index = data.habits.iter().position(|habit| habit.id == id)?;
+                let habit = data.habits.remove(index);
+                Some(Edit::InsertHabit { index, habit })
+            }
+            Edit::ReplaceHabit(habit) => {
+                let old = data.find_habit_mut(habit.id)?;
+                let records = std::mem::take(&mut old.records);
+                let mut previous = std::mem::replace(old, habit);
+                old.records = records;
+                previous.records.clear();
+                Some(Edit::ReplaceHabit(previous))
+            }
+            Edit::InsertRecord {
+                habit_id,
+                index,
+                record,
+            } => {
+                let id = record.id;
+                // Record IDs are unique across habits
+                if data
+                    .habits
+                    .iter()
+                    .any(|habit| habit.find_record(id).is_some())
+                {
+                    return None;
+                }
+                let habit = data.find_habit_mut(habit_id)?;
+                habit.records.insert(index.min(habit.records.len()), record);
+                Some(Edit::RemoveRecord { habit_id, id })
+            }
+            Edit::RemoveRecord { habit_id, id } => {
+                let habit = data.find_habit_mut(habit_id)?;
+                let index = habit.records.iter().position(|record| record.id == id)?;
+                let record = habit.records.remove(index);
+                Some(Edit::InsertRecord {
+                    habit_id,
+                    index,
+                    record,
+                })
+            }
+            Edit::ReplaceRecord { habit_id, record } => {
+                let habit = data.find_habit_mut(habit_id)?;
+                let old = habit.records.iter_mut().find(|old| old.id == record.id)?;
+                Some(Edit::ReplaceRecord {
+                    habit_id,
+                    record: std::mem::replace(old, record),
+                })
+            }
+            Edit::ReplaceAll(new_data) => Some(Edit::ReplaceAll(std::mem::replace(data, new_data))),
+            Edit::SetTimer(timer) => {
+                Some(Edit::SetTimer(std::mem::replace(&mut data.timer, timer)))
+            }
+            Edit::Batch(edits) => {
+                let mut inverses = Vec::new();
+                for edit in edits {
+                    match edit.apply(data) {
+                        Some(inverse) => inverses.push(inverse),
+                        None => {
+                            // Undo the ones that were applied
+                            for inverse in inverses.into_iter().rev() {
+                                inverse.apply(data);
+                            }
+                            return None;
+                        }
+                    }
+                }
+                inverses.reverse();
+                Some(Edit::Batch(inverses))
+            }
+        }
+    }
+}
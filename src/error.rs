@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::{Date, Time};
+
+#[derive(Debug)]
+pub enum AvalancheError {
+    Io(io::Error),
+    // Line and column are counted from 1, like ron does
+    Parse {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+    Serialize(ron::Error),
+    Validation(ValidationError),
+    // A validation error in imported data, with where it was found
+    Invalid {
+        location: String,
+        error: ValidationError,
+    },
+    VersionMismatch {
+        found: u16,
+        supported: u16,
+    },
+    // Another instance holds the lock on the data file
+    Locked {
+        pid: Option<u32>,
+    },
+    ChangedOnDisk,
+    // The data file was opened read-only, so it can't be saved
+    ReadOnly,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ValidationError {
+    InvalidDate(Date),
+    InvalidTime(Time),
+    EndBeforeStart,
+    // Longer than Time::MAX
+    TooLong,
+    // A date, time or number that couldn't be read at all
+    Unreadable(String),
+    // Habit names have to be unique
+    DuplicateName(String),
+    EmptyName,
+    // A record with fields the habit's kind doesn't use, or missing an
+    // amount it needs
+    WrongKind,
+}
+
+impl fmt::Display for AvalancheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AvalancheError::Io(error) => write!(f, "{}", error),
+            AvalancheError::Parse {
+                message,
+                line,
+                column,
+            } => write!(f, "Line {}, column {}: {}", line, column, message),
+            AvalancheError::Serialize(error) => write!(f, "Could not serialize data: {}", error),
+            AvalancheError::Validation(error) => write!(f, "{}", error),
+            AvalancheError::Invalid { location, error } => write!(f, "{}: {}", location, error),
+            AvalancheError::VersionMismatch { found, supported } if found > supported => write!(
+                f,
+                "The data file was written by a newer version of AVALANCHE \
+                 (file version {}, this version supports up to {}). \
+                 Update AVALANCHE to open it.",
+                found, supported
+            ),
+            AvalancheError::VersionMismatch { found, supported } => write!(
+                f,
+                "Can't upgrade the data file from version {} to {}",
+                found, supported
+            ),
+            AvalancheError::Locked { pid: Some(pid) } => write!(
+                f,
+                "The data file is open in another instance of AVALANCHE (process {})",
+                pid
+            ),
+            AvalancheError::Locked { pid: None } => {
+                write!(f, "The data file is open in another instance of AVALANCHE")
+            }
+            AvalancheError::ChangedOnDisk => write!(
+                f,
+                "The data file was changed by another program since it was loaded"
+            ),
+            AvalancheError::ReadOnly => write!(f, "The data file was opened read-only"),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::InvalidDate(date) => write!(
+                f,
+                "Invalid date {:04}-{:02}-{:02}",
+                date.year, date.month, date.day
+            ),
+            ValidationError::InvalidTime(time) => {
+                write!(f, "Invalid time {:02}:{:02}", time.hours, time.minutes)
+            }
+            ValidationError::EndBeforeStart => write!(f, "End time before start time"),
+            ValidationError::TooLong => write!(f, "Longer than {} hours", u16::MAX),
+            ValidationError::Unreadable(value) => write!(f, "Can't read \"{}\"", value),
+            ValidationError::DuplicateName(name) => {
+                write!(f, "More than one habit is named \"{}\"", name)
+            }
+            ValidationError::EmptyName => write!(f, "The name can't be empty"),
+            ValidationError::WrongKind => {
+                write!(f, "Doesn't fit the kind of habit")
+            }
+        }
+    }
+}
+
+impl Error for AvalancheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AvalancheError::Io(error) => Some(error),
+            AvalancheError::Serialize(error) => Some(error),
+            AvalancheError::Validation(error) => Some(error),
+            AvalancheError::Invalid { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl From<io::Error> for AvalancheError {
+    fn from(error: io::Error) -> Self {
+        AvalancheError::Io(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for AvalancheError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        AvalancheError::Parse {
+            message: error.code.to_string(),
+            line: error.position.line,
+            column: error.position.col,
+        }
+    }
+}
+
+impl From<ron::Error> for AvalancheError {
+    fn from(error: ron::Error) -> Self {
+        AvalancheError::Serialize(error)
+    }
+}
+
+impl From<ValidationError> for AvalancheError {
+    fn from(error: ValidationError) -> Self {
+        AvalancheError::Validation(error)
+    }
+}
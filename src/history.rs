@@ -0,0 +1,61 @@
+use crate::edit::Edit;
+use crate::UserData;
+
+/* Undo and redo. Each stack holds the edits that reverse what was done,
+ * so undoing is just applying the top of the undo stack and keeping its
+ * inverse for redo.
+ */
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Edit>,
+    redo_stack: Vec<Edit>,
+}
+
+impl History {
+    // Returns false without changing anything if the edit doesn't apply
+    pub fn apply(&mut self, edit: Edit, data: &mut UserData) -> bool {
+        match edit.apply(data) {
+            Some(inverse) => {
+                self.undo_stack.push(inverse);
+                // A new edit starts a new branch of history
+                self.redo_stack.clear();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Returns the edit that was applied, so it can be written to the journal
+    pub fn undo(&mut self, data: &mut UserData) -> Option<Edit> {
+        Self::step(&mut self.undo_stack, &mut self.redo_stack, data)
+    }
+
+    pub fn redo(&mut self, data: &mut UserData) -> Option<Edit> {
+        Self::step(&mut self.redo_stack, &mut self.undo_stack, data)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+    }
+
+    fn step(from: &mut Vec<Edit>, to: &mut Vec<Edit>, data: &mut UserData) -> Option<Edit> {
+        // Edits that no longer apply are dropped rather than blocking the
+        // rest of the history
+        while let Some(edit) = from.pop() {
+            if let Some(inverse) = edit.clone().apply(data) {
+                to.push(inverse);
+                return Some(edit);
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,150 @@
+/* Formats for moving habits and records to and from other programs. They
+ * sit beside the data file rather than replacing it.
+ */
+use std::fs;
+use std::io;
+
+use crate::{AvalancheError, Habit, HabitKind, Record, UserData};
+
+pub mod csv;
+pub mod ics;
+pub mod json;
+pub mod trackers;
+
+/* A format records can be imported from. Sources only read the file;
+ * merging what they read into the habits is the same for all of them.
+ */
+pub trait Source: Sync {
+    // Shown in menus, after "Import"
+    fn name(&self) -> &'static str;
+    // Suggested when asking for the file
+    fn file_name(&self) -> &'static str;
+    fn read(&self, reader: &mut dyn io::Read) -> Result<Imported, AvalancheError>;
+}
+
+pub static SOURCES: [&dyn Source; 3] = [
+    &csv::AvalancheCsv,
+    &trackers::LoopHabitTracker,
+    &trackers::DateHabitValue,
+];
+
+// What a source read: records with their line numbers and the names of
+// their habits, in the order they were in the file
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Imported {
+    pub records: Vec<(usize, String, Record)>,
+    pub errors: Vec<RowError>,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    // Habits that didn't exist yet and were created for the import
+    pub new_habits: Vec<String>,
+    // Records that were already there and were skipped
+    pub duplicates: usize,
+    pub errors: Vec<RowError>,
+}
+
+// A row that was skipped, with its line number in the imported file
+#[derive(Clone, PartialEq, Debug)]
+pub struct RowError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ImportReport {
+    // One line per skipped row, for showing to the user
+    pub fn describe_errors(&self) -> String {
+        self.errors
+            .iter()
+            .map(|error| format!("Line {}: {}\n", error.line, error.message))
+            .collect()
+    }
+}
+
+impl UserData {
+    /* Adds the records to the habits with the same names, creating habits
+     * that don't exist yet. Records a habit already has are skipped, and
+     * ones that don't fit its kind are reported as errors.
+     */
+    pub fn merge(&mut self, imported: Imported) -> ImportReport {
+        let mut report = ImportReport {
+            errors: imported.errors,
+            ..Default::default()
+        };
+        for (line, name, record) in imported.records {
+            let habit_id = match self.find_habit_by_name(&name) {
+                Some(id) => id,
+                None => {
+                    report.new_habits.push(name.clone());
+                    self.add_habit(Habit {
+                        name,
+                        kind: guess_kind(&record),
+                        ..Default::default()
+                    })
+                }
+            };
+            let habit = self.find_habit_mut(habit_id).unwrap();
+            if let Err(error) = habit.kind.check_record(&record) {
+                report.errors.push(RowError {
+                    line,
+                    message: format!("{}: {}", habit.name, error),
+                });
+            } else if habit.has_record_like(&record) {
+                report.duplicates += 1;
+            } else {
+                habit.add_record(record);
+                report.imported += 1;
+            }
+        }
+        report
+    }
+
+    pub fn import_from(
+        &mut self,
+        source: &dyn Source,
+        filename: &str,
+    ) -> Result<ImportReport, AvalancheError> {
+        let imported = source.read(&mut fs::File::open(filename)?)?;
+        Ok(self.merge(imported))
+    }
+
+    // What importing the file would do, without changing anything
+    pub fn dry_run(
+        &self,
+        source: &dyn Source,
+        filename: &str,
+    ) -> Result<ImportReport, AvalancheError> {
+        self.clone().import_from(source, filename)
+    }
+}
+
+impl Habit {
+    // Whether there's a record with the same dates, times and amount
+    pub fn has_record_like(&self, record: &Record) -> bool {
+        self.records.iter().any(|old| {
+            old.date == record.date
+                && old.end_day() == record.end_day()
+                && old.start_time == record.start_time
+                && old.end_time == record.end_time
+                && old.amount == record.amount
+        })
+    }
+}
+
+// Given to quantity habits made by an import, which can't know the unit
+pub const PLACEHOLDER_UNIT: &str = "units";
+
+// Guesses the kind of a new habit from what its first record has
+fn guess_kind(record: &Record) -> HabitKind {
+    if record.amount.is_some() {
+        HabitKind::Quantity {
+            unit: String::from(PLACEHOLDER_UNIT),
+        }
+    } else if record.start_time.is_none() {
+        HabitKind::CheckIn
+    } else {
+        HabitKind::Timed
+    }
+}
@@ -0,0 +1,286 @@
+use std::fs;
+use std::io;
+
+use crate::interchange::{ImportReport, Imported, RowError, Source};
+use crate::{new_id, AvalancheError, Date, Record, Time, UserData};
+
+/* One row per record. Dates are written as 2025-01-31, times as 17:05 and
+ * durations in minutes. Empty cells are missing values, and end_date is
+ * only filled in for records that end on a later day.
+ */
+pub const HEADERS: [&str; 8] = [
+    "habit", "date", "start", "end_date", "end", "duration", "note", "amount",
+];
+
+// Which column holds each field, counted from 0
+#[derive(Clone, PartialEq, Debug)]
+pub struct ColumnMap {
+    pub habit: usize,
+    pub date: usize,
+    pub start: Option<usize>,
+    pub end_date: Option<usize>,
+    pub end: Option<usize>,
+    // Used to work out the end when there's no end column, and the end
+    // date when there's no end date column
+    pub duration: Option<usize>,
+    pub note: Option<usize>,
+    pub amount: Option<usize>,
+}
+
+impl Default for ColumnMap {
+    // The columns written by export
+    fn default() -> Self {
+        ColumnMap {
+            habit: 0,
+            date: 1,
+            start: Some(2),
+            end_date: Some(3),
+            end: Some(4),
+            duration: Some(5),
+            note: Some(6),
+            amount: Some(7),
+        }
+    }
+}
+
+impl ColumnMap {
+    /* Matches headers to fields, ignoring case and a few different names
+     * for each. Returns None if there's no column for the habit or date.
+     */
+    pub fn from_headers(headers: &[&str]) -> Option<ColumnMap> {
+        let find = |names: &[&str]| {
+            headers.iter().position(|header| {
+                let header = header.trim().to_lowercase();
+                names.contains(&header.as_str())
+            })
+        };
+        Some(ColumnMap {
+            habit: find(&["habit", "habit name", "name"])?,
+            date: find(&["date", "day"])?,
+            start: find(&["start", "start time"]),
+            end_date: find(&["end_date", "end date"]),
+            end: find(&["end", "end time"]),
+            duration: find(&["duration", "minutes"]),
+            note: find(&["note", "notes", "comment"]),
+            amount: find(&["amount", "value", "quantity"]),
+        })
+    }
+}
+
+// Only I/O failures are Io. Columns are counted from 1, where known.
+impl From<::csv::Error> for AvalancheError {
+    fn from(error: ::csv::Error) -> Self {
+        let line = error
+            .position()
+            .map_or(1, |position| position.line() as usize);
+        let field = match error.kind() {
+            ::csv::ErrorKind::Io(_) => return AvalancheError::Io(error.into()),
+            ::csv::ErrorKind::Utf8 { err, .. } => Some(err.field()),
+            ::csv::ErrorKind::Deserialize { err, .. } => err.field().map(|field| field as usize),
+            ::csv::ErrorKind::UnequalLengths { .. } => None,
+            _ => return AvalancheError::Io(error.into()),
+        };
+        AvalancheError::Parse {
+            message: error.to_string(),
+            line,
+            column: field.map_or(1, |field| field + 1),
+        }
+    }
+}
+
+impl UserData {
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> Result<(), AvalancheError> {
+        let time_cell =
+            |time: &Option<Time>| time.as_ref().map(Time::to_string).unwrap_or_default();
+        let mut writer = ::csv::Writer::from_writer(writer);
+        writer.write_record(HEADERS)?;
+        for habit in &self.habits {
+            for record in &habit.records {
+                let duration = match (&record.start_time, &record.end_time, record.length()) {
+                    (Some(_), Some(_), Ok(length)) => length.as_minutes().to_string(),
+                    _ => String::new(),
+                };
+                let end_date = match &record.end_date {
+                    Some(end_date) if *end_date != record.date => end_date.to_string(),
+                    _ => String::new(),
+                };
+                writer.write_record([
+                    habit.name.clone(),
+                    record.date.to_string(),
+                    time_cell(&record.start_time),
+                    end_date,
+                    time_cell(&record.end_time),
+                    duration,
+                    record.note.clone(),
+                    record
+                        .amount
+                        .map(|amount| amount.to_string())
+                        .unwrap_or_default(),
+                ])?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn export_csv(&self, filename: &str) -> Result<(), AvalancheError> {
+        self.write_csv(fs::File::create(filename)?)
+    }
+
+    /* Adds the records in the CSV to the habits with the same names,
+     * creating habits that don't exist yet. Rows that can't be read are
+     * skipped and listed in the report. Without columns they're found from
+     * the header.
+     */
+    pub fn read_csv<R: io::Read>(
+        &mut self,
+        reader: R,
+        columns: Option<&ColumnMap>,
+    ) -> Result<ImportReport, AvalancheError> {
+        Ok(self.merge(read_records(reader, columns)?))
+    }
+
+    pub fn import_csv(
+        &mut self,
+        filename: &str,
+        columns: Option<&ColumnMap>,
+    ) -> Result<ImportReport, AvalancheError> {
+        self.read_csv(fs::File::open(filename)?, columns)
+    }
+}
+
+// CSV files written by export, or with columns that can be found from the
+// header
+pub struct AvalancheCsv;
+
+impl Source for AvalancheCsv {
+    fn name(&self) -> &'static str {
+        "CSV"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "avalanche.csv"
+    }
+
+    fn read(&self, reader: &mut dyn io::Read) -> Result<Imported, AvalancheError> {
+        read_records(reader, None)
+    }
+}
+
+fn read_records<R: io::Read>(
+    reader: R,
+    columns: Option<&ColumnMap>,
+) -> Result<Imported, AvalancheError> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(reader);
+    let mut imported = Imported::default();
+
+    let headers = reader.headers()?.clone();
+    let columns = match columns {
+        Some(columns) => columns.clone(),
+        None => match ColumnMap::from_headers(&headers.iter().collect::<Vec<&str>>()) {
+            Some(columns) => columns,
+            None => {
+                imported.errors.push(RowError {
+                    line: 1,
+                    message: String::from("The header has no habit or date column"),
+                });
+                return Ok(imported);
+            }
+        },
+    };
+
+    for (index, row) in reader.records().enumerate() {
+        // Lines are counted from 1 and the header is the first
+        let mut line = index + 2;
+        let result = row.map_err(|error| error.to_string()).and_then(|row| {
+            if let Some(position) = row.position() {
+                line = position.line() as usize;
+            }
+            parse_row(&row, &columns)
+        });
+        match result {
+            Ok((name, record)) => imported.records.push((line, name, record)),
+            Err(message) => imported.errors.push(RowError { line, message }),
+        }
+    }
+    Ok(imported)
+}
+
+fn parse_row(row: &::csv::StringRecord, columns: &ColumnMap) -> Result<(String, Record), String> {
+    let cell = |column: Option<usize>| {
+        column
+            .and_then(|column| row.get(column))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    };
+    let parse_time = |column: Option<usize>, label: &str| {
+        cell(column)
+            .map(|value| value.parse::<Time>())
+            .transpose()
+            .map_err(|error| format!("{}: {}", label, error))
+    };
+
+    let name = cell(Some(columns.habit)).ok_or("No habit name")?;
+    let date: Date = cell(Some(columns.date))
+        .ok_or("No date")?
+        .parse()
+        .map_err(|error| format!("Date: {}", error))?;
+    let start_time = parse_time(columns.start, "Start")?;
+    let mut end_date = cell(columns.end_date)
+        .map(|value| value.parse::<Date>())
+        .transpose()
+        .map_err(|error| format!("End date: {}", error))?;
+    let mut end_time = parse_time(columns.end, "End")?;
+    let duration = cell(columns.duration)
+        .map(|value| value.parse::<u32>())
+        .transpose()
+        .map_err(|_| String::from("Duration should be a whole number of minutes"))?;
+    if duration.is_some_and(|duration| duration > Time::MAX.as_minutes()) {
+        return Err(String::from("Duration is too long"));
+    }
+    let amount = cell(columns.amount)
+        .map(|value| value.parse::<f64>())
+        .transpose()
+        .map_err(|_| String::from("Amount should be a number"))?;
+    if amount.is_some_and(|amount| !amount.is_finite() || amount < 0.0) {
+        return Err(String::from("Amount can't be negative"));
+    }
+
+    /* Without an end date it's worked out from the duration, which can be
+     * more than a day, or failing that records that end earlier in the day
+     * than they start go past midnight.
+     */
+    if let (Some(start), Some(duration)) = (&start_time, duration) {
+        let end = start
+            .as_minutes()
+            .checked_add(duration)
+            .ok_or("Duration is too long")?;
+        if end_time.is_none() {
+            end_time = Some(Time::of_day(end));
+        }
+        if end_date.is_none() && end >= 1440 {
+            end_date = Some(date.add_days((end / 1440) as i64));
+        }
+    } else if let (Some(start), Some(end), None) = (&start_time, &end_time, &end_date) {
+        if end.as_minutes() < start.as_minutes() {
+            end_date = Some(date.add_days(1));
+        }
+    }
+    // The same day is written as no end date
+    end_date = end_date.filter(|end_date| *end_date != date);
+
+    let record = Record {
+        id: new_id(),
+        note: cell(columns.note).unwrap_or("").to_string(),
+        date,
+        start_time,
+        end_time,
+        end_date,
+        amount,
+        pomodoro: false,
+    };
+    record.validate().map_err(|error| error.to_string())?;
+    Ok((String::from(name), record))
+}
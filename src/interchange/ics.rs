@@ -0,0 +1,444 @@
+use chrono::{Datelike, TimeZone, Timelike};
+use std::fs;
+use std::io;
+
+use crate::interchange::{ImportReport, RowError};
+use crate::{AvalancheError, Date, Habit, HabitKind, Record, Time, UserData, ValidationError};
+
+/* iCalendar (RFC 5545) files, for looking at records in a calendar app.
+ * Each record is a VEVENT with the habit's name as the summary and the note
+ * as the description. Records with times are written as floating local
+ * times, and ones without as all-day events.
+ */
+const PRODUCT_ID: &str = "-//AVALANCHE//Habit tracker//EN";
+
+// Record IDs are kept in the UID, so exporting and importing again finds
+// the same records
+const UID_DOMAIN: &str = "@avalanche";
+
+impl UserData {
+    pub fn write_ics<W: io::Write>(&self, mut writer: W) -> Result<(), AvalancheError> {
+        let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let mut lines = vec![
+            String::from("BEGIN:VCALENDAR"),
+            String::from("VERSION:2.0"),
+            format!("PRODID:{}", PRODUCT_ID),
+        ];
+        for habit in &self.habits {
+            for record in &habit.records {
+                lines.push(String::from("BEGIN:VEVENT"));
+                lines.push(format!("UID:{}{}", record.id, UID_DOMAIN));
+                lines.push(format!("DTSTAMP:{}", stamp));
+                match (&record.start_time, &record.end_time) {
+                    (Some(start), end) => {
+                        lines.push(format!("DTSTART:{}", date_time_value(&record.date, start)));
+                        if let Some(end) = end {
+                            let value = date_time_value(record.end_day(), end);
+                            lines.push(format!("DTEND:{}", value));
+                        }
+                    }
+                    // All-day events end on the day after
+                    (None, _) => {
+                        lines.push(format!("DTSTART;VALUE=DATE:{}", date_value(&record.date)));
+                        let end = date_value(&record.date.add_days(1));
+                        lines.push(format!("DTEND;VALUE=DATE:{}", end));
+                    }
+                }
+                lines.push(format!("SUMMARY:{}", escape(&habit.name)));
+                if !record.note.is_empty() {
+                    lines.push(format!("DESCRIPTION:{}", escape(&record.note)));
+                }
+                lines.push(String::from("END:VEVENT"));
+            }
+        }
+        lines.push(String::from("END:VCALENDAR"));
+
+        for line in lines {
+            writer.write_all(fold(&line).as_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn export_ics(&self, filename: &str) -> Result<(), AvalancheError> {
+        self.write_ics(fs::File::create(filename)?)
+    }
+
+    // Imports the events into the habit with the given ID
+    pub fn import_ics(
+        &mut self,
+        filename: &str,
+        habit_id: u64,
+    ) -> Result<ImportReport, AvalancheError> {
+        let file = fs::File::open(filename)?;
+        match self.find_habit_mut(habit_id) {
+            Some(habit) => habit.read_ics(file, utc_to_local),
+            None => Ok(ImportReport::default()),
+        }
+    }
+}
+
+impl Habit {
+    /* Adds a record for each VEVENT. Events that are already records of
+     * the habit (by UID, or by having the same dates and times) are counted
+     * as duplicates and skipped. to_local converts UTC times to local ones.
+     * Events are time spent, so only timed habits can take them.
+     */
+    pub fn read_ics<R: io::Read>(
+        &mut self,
+        mut reader: R,
+        to_local: fn(Date, Time) -> (Date, Time),
+    ) -> Result<ImportReport, AvalancheError> {
+        if self.kind != HabitKind::Timed {
+            return Err(AvalancheError::Invalid {
+                location: self.name.clone(),
+                error: ValidationError::WrongKind,
+            });
+        }
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        let mut report = ImportReport::default();
+
+        let mut event: Option<Event> = None;
+        for (line, content) in unfold(&contents) {
+            let Some(property) = Property::parse(&content) else {
+                continue;
+            };
+            match (
+                property.name.as_str(),
+                property.value.to_uppercase().as_str(),
+            ) {
+                ("BEGIN", "VEVENT") => {
+                    event = Some(Event {
+                        line,
+                        ..Default::default()
+                    })
+                }
+                ("END", "VEVENT") => {
+                    let Some(event) = event.take() else {
+                        continue;
+                    };
+                    let line = event.line;
+                    match event.to_record(&self.name, to_local) {
+                        Ok((uid, record)) if self.has_duplicate(uid, &record) => {
+                            report.duplicates += 1
+                        }
+                        Ok((_, record)) => {
+                            self.add_record(record);
+                            report.imported += 1;
+                        }
+                        Err(message) => report.errors.push(RowError { line, message }),
+                    }
+                }
+                _ => {
+                    if let Some(event) = &mut event {
+                        event.properties.push(property);
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    fn has_duplicate(&self, uid: Option<u64>, record: &Record) -> bool {
+        uid.is_some_and(|id| self.find_record(id).is_some()) || self.has_record_like(record)
+    }
+}
+
+// Local time of the system at a UTC time
+pub fn utc_to_local(date: Date, time: Time) -> (Date, Time) {
+    let utc = chrono::Utc.with_ymd_and_hms(
+        date.year as i32,
+        date.month as u32,
+        date.day as u32,
+        time.hours as u32,
+        time.minutes as u32,
+        0,
+    );
+    match utc.single() {
+        Some(utc) => {
+            let local = utc.with_timezone(&chrono::Local);
+            (
+                Date {
+                    year: local.year() as u16,
+                    month: local.month() as u8,
+                    day: local.day() as u8,
+                },
+                Time {
+                    hours: local.hour() as u16,
+                    minutes: local.minute() as u16,
+                },
+            )
+        }
+        None => (date, time),
+    }
+}
+
+#[derive(Default)]
+struct Event {
+    // Where BEGIN:VEVENT is, for errors
+    line: usize,
+    properties: Vec<Property>,
+}
+
+struct Property {
+    name: String,
+    // Parameter names are upper case
+    parameters: Vec<(String, String)>,
+    value: String,
+}
+
+// A date, and a time unless it's an all-day value
+type DateTime = (Date, Option<Time>);
+
+impl Property {
+    // NAME;PARAMETER=value:value. Colons in quoted parameters don't count.
+    fn parse(line: &str) -> Option<Property> {
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(index, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(index),
+            _ => None,
+        })?;
+        let mut parts = line[..colon].split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let parameters = parts
+            .filter_map(|parameter| parameter.split_once('='))
+            .map(|(name, value)| (name.to_uppercase(), value.trim_matches('"').to_string()))
+            .collect();
+        Some(Property {
+            name,
+            parameters,
+            value: line[colon + 1..].to_string(),
+        })
+    }
+
+    fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(parameter, _)| parameter == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /* Dates, floating local times and UTC times (ending in Z). Times with a
+     * TZID are read as local, since there's no time zone database to convert
+     * them with.
+     */
+    fn date_time(&self, to_local: fn(Date, Time) -> (Date, Time)) -> Result<DateTime, String> {
+        let unreadable = || format!("{}: Can't read \"{}\"", self.name, self.value);
+        let value = self.value.trim();
+        let number = |range: std::ops::Range<usize>| {
+            value
+                .get(range)
+                .filter(|digits| digits.bytes().all(|c| c.is_ascii_digit()))
+                .and_then(|digits| digits.parse::<u16>().ok())
+                .ok_or_else(unreadable)
+        };
+        let date = Date {
+            year: number(0..4)?,
+            month: number(4..6)? as u8,
+            day: number(6..8)? as u8,
+        };
+        if !date.is_valid() {
+            return Err(unreadable());
+        }
+        if value.len() == 8 || self.parameter("VALUE") == Some("DATE") {
+            return Ok((date, None));
+        }
+        if value.as_bytes().get(8) != Some(&b'T') {
+            return Err(unreadable());
+        }
+        // Seconds are dropped
+        let time = Time {
+            hours: number(9..11)?,
+            minutes: number(11..13)?,
+        };
+        if !time.is_valid_time_of_day() {
+            return Err(unreadable());
+        }
+        // The time is all ASCII, so the rest starts on a char boundary
+        let rest = &value[13..];
+        let rest = match rest.get(..2) {
+            Some(seconds) if seconds.bytes().all(|c| c.is_ascii_digit()) => &rest[2..],
+            _ => rest,
+        };
+        match rest {
+            "Z" => {
+                let (date, time) = to_local(date, time);
+                Ok((date, Some(time)))
+            }
+            "" => Ok((date, Some(time))),
+            _ => Err(unreadable()),
+        }
+    }
+}
+
+impl Event {
+    fn property(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    // The record, and the record ID from the UID if it was exported by
+    // AVALANCHE
+    fn to_record(
+        &self,
+        habit_name: &str,
+        to_local: fn(Date, Time) -> (Date, Time),
+    ) -> Result<(Option<u64>, Record), String> {
+        let (date, start_time) = self
+            .property("DTSTART")
+            .ok_or("No DTSTART")?
+            .date_time(to_local)?;
+        let mut end = match self.property("DTEND") {
+            Some(property) => Some(property.date_time(to_local)?),
+            None => None,
+        };
+        if let (Some(start), None, Some(duration)) = (&start_time, &end, self.property("DURATION"))
+        {
+            let minutes = parse_duration(&duration.value)
+                .ok_or_else(|| format!("DURATION: Can't read \"{}\"", duration.value))?;
+            let end_minutes = start
+                .as_minutes()
+                .checked_add(minutes)
+                .ok_or_else(|| format!("DURATION: \"{}\" is too long", duration.value))?;
+            end = Some((
+                date.add_days((end_minutes / 1440) as i64),
+                Some(Time::of_day(end_minutes)),
+            ));
+        }
+
+        // All-day events become records on their first day
+        let (end_date, end_time) = match (&start_time, end) {
+            (Some(_), Some((end_date, Some(end_time)))) => {
+                (Some(end_date).filter(|end| *end != date), Some(end_time))
+            }
+            _ => (None, None),
+        };
+
+        let text = |name: &str| {
+            self.property(name)
+                .map(|property| unescape(&property.value))
+        };
+        // Events from other calendars have what they were in the summary
+        let note = match (text("DESCRIPTION"), text("SUMMARY")) {
+            (Some(description), _) => description,
+            (None, Some(summary)) if summary != habit_name => summary,
+            _ => String::new(),
+        };
+        let uid = self
+            .property("UID")
+            .and_then(|uid| uid.value.strip_suffix(UID_DOMAIN))
+            .and_then(|id| id.parse().ok());
+
+        let record = Record {
+            id: 0,
+            note,
+            date,
+            start_time,
+            end_time,
+            end_date,
+            amount: None,
+            pomodoro: false,
+        };
+        record.validate().map_err(|error| error.to_string())?;
+        Ok((uid, record))
+    }
+}
+
+// Durations like PT1H30M or P1D, in minutes. Weeks and seconds are allowed
+// but negative durations aren't.
+fn parse_duration(value: &str) -> Option<u32> {
+    let value = value.trim().strip_prefix('+').unwrap_or(value.trim());
+    let mut minutes: u32 = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() => in_time = true,
+            _ => {
+                let n: u32 = number.parse().ok()?;
+                number.clear();
+                let part = match (c, in_time) {
+                    ('W', false) => n.checked_mul(7 * 1440),
+                    ('D', false) => n.checked_mul(1440),
+                    ('H', true) => n.checked_mul(60),
+                    ('M', true) => Some(n),
+                    ('S', true) => Some(n / 60),
+                    _ => return None,
+                };
+                minutes = part.and_then(|part| minutes.checked_add(part))?;
+            }
+        }
+    }
+    number.is_empty().then_some(minutes)
+}
+
+// Joins folded lines, returning each with the line number it starts on
+fn unfold(contents: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+    lines
+}
+
+// Lines are at most 75 bytes, not counting the line break
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded + "\r\n"
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+fn date_value(date: &Date) -> String {
+    format!("{:04}{:02}{:02}", date.year, date.month, date.day)
+}
+
+fn date_time_value(date: &Date, time: &Time) -> String {
+    format!(
+        "{}T{:02}{:02}00",
+        date_value(date),
+        time.hours,
+        time.minutes
+    )
+}
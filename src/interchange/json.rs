@@ -0,0 +1,524 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs;
+
+use crate::interchange::ImportReport;
+use crate::{
+    new_id, AvalancheError, Date, Goals, Habit, HabitKind, Record, Schedule, Time, UserData,
+    ValidationError, Weekday,
+};
+
+/* The JSON interchange format. It has its own types so the data file can
+ * change without breaking other tools, and format_version only goes up
+ * when a change would. schema() describes it.
+ *
+ * IDs are strings because they don't fit in the integers JavaScript can
+ * represent exactly. Dates are written as 2025-01-31 and times as 17:05.
+ */
+pub const FORMAT_VERSION: u16 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct JsonData {
+    format_version: u16,
+    habits: Vec<JsonHabit>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonHabit {
+    #[serde(default)]
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    kind: JsonKind,
+    #[serde(default)]
+    schedule: JsonSchedule,
+    #[serde(default)]
+    goals: JsonGoals,
+    #[serde(default)]
+    start_date: Option<String>,
+    #[serde(default)]
+    records: Vec<JsonRecord>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonKind {
+    #[default]
+    Timed,
+    Quit,
+    Quantity {
+        unit: String,
+    },
+    CheckIn,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonSchedule {
+    #[default]
+    Daily,
+    Weekdays {
+        days: Vec<Weekday>,
+    },
+    EveryNDays {
+        days: u16,
+    },
+    TimesPerWeek {
+        times: u8,
+    },
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct JsonGoals {
+    #[serde(default)]
+    daily_minutes: Option<u32>,
+    #[serde(default)]
+    weekly_minutes: Option<u32>,
+    #[serde(default)]
+    monthly_minutes: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonRecord {
+    #[serde(default)]
+    id: Option<String>,
+    date: String,
+    #[serde(default)]
+    end_date: Option<String>,
+    #[serde(default)]
+    start: Option<String>,
+    #[serde(default)]
+    end: Option<String>,
+    #[serde(default)]
+    amount: Option<f64>,
+    #[serde(default)]
+    note: String,
+    #[serde(default)]
+    pomodoro: bool,
+}
+
+impl From<serde_json::Error> for AvalancheError {
+    fn from(error: serde_json::Error) -> Self {
+        AvalancheError::Parse {
+            message: error.to_string(),
+            line: error.line(),
+            column: error.column(),
+        }
+    }
+}
+
+// Converts to and from the interchange format
+pub trait Json: Sized {
+    fn to_value(&self) -> Result<serde_json::Value, AvalancheError>;
+    fn from_json(json: &str) -> Result<Self, AvalancheError>;
+
+    fn to_json(&self) -> Result<String, AvalancheError> {
+        Ok(serde_json::to_string_pretty(&self.to_value()?)?)
+    }
+}
+
+impl Json for UserData {
+    fn to_value(&self) -> Result<serde_json::Value, AvalancheError> {
+        let data = JsonData {
+            format_version: FORMAT_VERSION,
+            habits: self.habits.iter().map(habit_to_json).collect(),
+        };
+        Ok(serde_json::to_value(data)?)
+    }
+
+    fn from_json(json: &str) -> Result<UserData, AvalancheError> {
+        let data: JsonData = serde_json::from_str(json)?;
+        if data.format_version > FORMAT_VERSION {
+            return Err(AvalancheError::VersionMismatch {
+                found: data.format_version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        let habits = data
+            .habits
+            .into_iter()
+            .enumerate()
+            .map(|(index, habit)| habit_from_json(habit, &format!("habits[{}]", index)))
+            .collect::<Result<Vec<Habit>, AvalancheError>>()?;
+        Ok(UserData {
+            version: UserData::CURRENT_VERSION,
+            habits,
+            timer: None,
+        })
+    }
+}
+
+impl Json for Habit {
+    fn to_value(&self) -> Result<serde_json::Value, AvalancheError> {
+        Ok(serde_json::to_value(habit_to_json(self))?)
+    }
+
+    fn from_json(json: &str) -> Result<Habit, AvalancheError> {
+        habit_from_json(serde_json::from_str(json)?, "habit")
+    }
+}
+
+impl Json for Record {
+    fn to_value(&self) -> Result<serde_json::Value, AvalancheError> {
+        Ok(serde_json::to_value(record_to_json(self))?)
+    }
+
+    fn from_json(json: &str) -> Result<Record, AvalancheError> {
+        record_from_json(serde_json::from_str(json)?, "record")
+    }
+}
+
+impl UserData {
+    pub fn export_json(&self, filename: &str) -> Result<(), AvalancheError> {
+        fs::write(filename, self.to_json()?)?;
+        Ok(())
+    }
+
+    /* Habits in the file update the ones with the same ID, or failing that
+     * the same name, and the rest are added. Records are merged the same
+     * way, by ID. Nothing is changed if the import would leave two habits
+     * with the same name.
+     */
+    pub fn import_json(&mut self, filename: &str) -> Result<ImportReport, AvalancheError> {
+        let imported = UserData::from_json(&fs::read_to_string(filename)?)?;
+        let duplicate = |index: usize, name: String| AvalancheError::Invalid {
+            location: format!("habits[{}].name", index),
+            error: ValidationError::DuplicateName(name),
+        };
+        for (index, habit) in imported.habits.iter().enumerate() {
+            if imported.habits[..index]
+                .iter()
+                .any(|other| other.name == habit.name)
+            {
+                return Err(duplicate(index, habit.name.clone()));
+            }
+        }
+
+        let mut merged = self.clone();
+        let mut report = ImportReport::default();
+        for (index, habit) in imported.habits.into_iter().enumerate() {
+            if let Some(name) = merged.merge_habit(habit, &mut report) {
+                return Err(duplicate(index, name));
+            }
+        }
+        *self = merged;
+        Ok(report)
+    }
+
+    // Returns the name if it's now used by more than one habit
+    fn merge_habit(&mut self, mut habit: Habit, report: &mut ImportReport) -> Option<String> {
+        let existing = self
+            .habits
+            .iter()
+            .position(|old| old.id == habit.id)
+            .or_else(|| self.habits.iter().position(|old| old.name == habit.name));
+        // Record IDs are unique across habits, so records with the ID of one
+        // under another habit are added as new ones
+        let habit_id = existing.map(|index| self.habits[index].id);
+        for record in &mut habit.records {
+            if self
+                .habits
+                .iter()
+                .any(|other| Some(other.id) != habit_id && other.find_record(record.id).is_some())
+            {
+                record.id = new_id();
+            }
+        }
+        let Some(index) = existing else {
+            report.imported += habit.records.len();
+            report.new_habits.push(habit.name.clone());
+            self.habits.push(habit);
+            return None;
+        };
+
+        let old = &mut self.habits[index];
+        old.name = habit.name;
+        old.kind = habit.kind;
+        old.schedule = habit.schedule;
+        old.goals = habit.goals;
+        old.start_date = habit.start_date;
+        for record in habit.records {
+            match old.records.iter_mut().find(|old| old.id == record.id) {
+                Some(old) if *old == record => report.duplicates += 1,
+                Some(old) => {
+                    *old = record;
+                    report.imported += 1;
+                }
+                None => {
+                    old.records.push(record);
+                    report.imported += 1;
+                }
+            }
+        }
+
+        let name = &self.habits[index].name;
+        if self
+            .habits
+            .iter()
+            .filter(|other| other.name == *name)
+            .count()
+            > 1
+        {
+            return Some(name.clone());
+        }
+        None
+    }
+}
+
+fn habit_to_json(habit: &Habit) -> JsonHabit {
+    let minutes = |goal: &Option<Time>| goal.as_ref().map(Time::as_minutes);
+    JsonHabit {
+        id: Some(habit.id.to_string()),
+        name: habit.name.clone(),
+        kind: match &habit.kind {
+            HabitKind::Timed => JsonKind::Timed,
+            HabitKind::Quit => JsonKind::Quit,
+            HabitKind::Quantity { unit } => JsonKind::Quantity { unit: unit.clone() },
+            HabitKind::CheckIn => JsonKind::CheckIn,
+        },
+        schedule: match &habit.schedule {
+            Schedule::Daily => JsonSchedule::Daily,
+            Schedule::Weekdays(days) => JsonSchedule::Weekdays { days: days.clone() },
+            Schedule::EveryNDays(days) => JsonSchedule::EveryNDays { days: *days },
+            Schedule::TimesPerWeek(times) => JsonSchedule::TimesPerWeek { times: *times },
+        },
+        goals: JsonGoals {
+            daily_minutes: minutes(&habit.goals.daily),
+            weekly_minutes: minutes(&habit.goals.weekly),
+            monthly_minutes: minutes(&habit.goals.monthly),
+        },
+        start_date: habit.start_date.as_ref().map(Date::to_string),
+        records: habit.records.iter().map(record_to_json).collect(),
+    }
+}
+
+fn record_to_json(record: &Record) -> JsonRecord {
+    JsonRecord {
+        id: Some(record.id.to_string()),
+        date: record.date.to_string(),
+        end_date: record.end_date.as_ref().map(Date::to_string),
+        start: record.start_time.as_ref().map(Time::to_string),
+        end: record.end_time.as_ref().map(Time::to_string),
+        amount: record.amount,
+        note: record.note.clone(),
+        pomodoro: record.pomodoro,
+    }
+}
+
+// Missing IDs get new ones
+fn id_from_json(id: Option<String>, location: &str) -> Result<u64, AvalancheError> {
+    match id {
+        None => Ok(new_id()),
+        Some(id) => match id.parse() {
+            Ok(0) | Err(_) => Err(invalid(location, ValidationError::Unreadable(id))),
+            Ok(id) => Ok(id),
+        },
+    }
+}
+
+fn invalid(location: &str, error: ValidationError) -> AvalancheError {
+    AvalancheError::Invalid {
+        location: String::from(location),
+        error,
+    }
+}
+
+// Puts where the value came from on validation errors
+fn located<T>(result: Result<T, AvalancheError>, location: &str) -> Result<T, AvalancheError> {
+    result.map_err(|error| match error {
+        AvalancheError::Validation(error) => invalid(location, error),
+        other => other,
+    })
+}
+
+fn habit_from_json(habit: JsonHabit, location: &str) -> Result<Habit, AvalancheError> {
+    let minutes = |minutes: Option<u32>| {
+        minutes
+            .map(|minutes| {
+                Time::from_minutes(minutes).ok_or_else(|| {
+                    invalid(&format!("{}.goals", location), ValidationError::TooLong)
+                })
+            })
+            .transpose()
+    };
+    let schedule = match habit.schedule {
+        JsonSchedule::Daily => Schedule::Daily,
+        JsonSchedule::Weekdays { days } => Schedule::Weekdays(days),
+        JsonSchedule::EveryNDays { days: 0 } => {
+            return Err(invalid(
+                &format!("{}.schedule", location),
+                ValidationError::Unreadable(String::from("0")),
+            ))
+        }
+        JsonSchedule::EveryNDays { days } => Schedule::EveryNDays(days),
+        JsonSchedule::TimesPerWeek { times } if (1..=7).contains(&times) => {
+            Schedule::TimesPerWeek(times)
+        }
+        JsonSchedule::TimesPerWeek { times } => {
+            return Err(invalid(
+                &format!("{}.schedule", location),
+                ValidationError::Unreadable(times.to_string()),
+            ))
+        }
+    };
+    let start_date = habit
+        .start_date
+        .map(|date| located(date.parse(), &format!("{}.start_date", location)))
+        .transpose()?;
+    let records = habit
+        .records
+        .into_iter()
+        .enumerate()
+        .map(|(index, record)| {
+            record_from_json(record, &format!("{}.records[{}]", location, index))
+        })
+        .collect::<Result<Vec<Record>, AvalancheError>>()?;
+
+    if habit.name.trim().is_empty() {
+        return Err(invalid(
+            &format!("{}.name", location),
+            ValidationError::EmptyName,
+        ));
+    }
+    Ok(Habit {
+        id: id_from_json(habit.id, &format!("{}.id", location))?,
+        name: habit.name,
+        records,
+        goals: Goals {
+            daily: minutes(habit.goals.daily_minutes)?,
+            weekly: minutes(habit.goals.weekly_minutes)?,
+            monthly: minutes(habit.goals.monthly_minutes)?,
+        },
+        schedule,
+        kind: match habit.kind {
+            JsonKind::Timed => HabitKind::Timed,
+            JsonKind::Quit => HabitKind::Quit,
+            JsonKind::Quantity { unit } => HabitKind::Quantity { unit },
+            JsonKind::CheckIn => HabitKind::CheckIn,
+        },
+        start_date,
+    })
+}
+
+fn record_from_json(record: JsonRecord, location: &str) -> Result<Record, AvalancheError> {
+    let parse_date = |date: String| located(date.parse::<Date>(), location);
+    let parse_time = |time: String| located(time.parse::<Time>(), location);
+    let record = Record {
+        id: id_from_json(record.id, &format!("{}.id", location))?,
+        note: record.note,
+        date: parse_date(record.date)?,
+        start_time: record.start.map(parse_time).transpose()?,
+        end_time: record.end.map(parse_time).transpose()?,
+        end_date: record.end_date.map(parse_date).transpose()?,
+        amount: record.amount,
+        pomodoro: record.pomodoro,
+    };
+    if let Some(amount) = record
+        .amount
+        .filter(|amount| !amount.is_finite() || *amount < 0.0)
+    {
+        return Err(invalid(
+            location,
+            ValidationError::Unreadable(amount.to_string()),
+        ));
+    }
+    located(record.validate(), location)?;
+    Ok(record)
+}
+
+// JSON Schema (draft 2020-12) for the format, generated from the same
+// names as the types above
+pub fn schema() -> serde_json::Value {
+    let weekdays: Vec<String> = Weekday::ALL
+        .iter()
+        .map(|day| {
+            serde_json::to_value(day)
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    let date = json!({ "type": "string", "pattern": "^[0-9]{1,4}-[0-9]{1,2}-[0-9]{1,2}$" });
+    let time = json!({ "type": "string", "pattern": "^[0-9]{1,2}:[0-9]{1,2}$" });
+    let id = json!({ "type": "string", "pattern": "^[0-9]+$" });
+    let nullable = |schema: &serde_json::Value| json!({ "anyOf": [schema, { "type": "null" }] });
+    let minutes = json!({ "anyOf": [{ "type": "integer", "minimum": 0 }, { "type": "null" }] });
+    let tagged = |tag: &str, properties: serde_json::Value| {
+        let mut required = vec![json!("type")];
+        let mut all = serde_json::Map::new();
+        all.insert(String::from("type"), json!({ "const": tag }));
+        if let serde_json::Value::Object(properties) = properties {
+            for (name, schema) in properties {
+                required.push(json!(name));
+                all.insert(name, schema);
+            }
+        }
+        json!({ "type": "object", "properties": all, "required": required })
+    };
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "AVALANCHE habits",
+        "description": "Habits and records exported from AVALANCHE",
+        "type": "object",
+        "properties": {
+            "format_version": { "const": FORMAT_VERSION },
+            "habits": { "type": "array", "items": { "$ref": "#/$defs/habit" } }
+        },
+        "required": ["format_version", "habits"],
+        "$defs": {
+            "date": date,
+            "time": time,
+            "habit": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "name": { "type": "string", "pattern": "\\S" },
+                    "kind": { "oneOf": [
+                        tagged("timed", json!({})),
+                        tagged("quit", json!({})),
+                        tagged("quantity", json!({ "unit": { "type": "string" } })),
+                        tagged("check_in", json!({}))
+                    ] },
+                    "schedule": { "oneOf": [
+                        tagged("daily", json!({})),
+                        tagged("weekdays", json!({ "days": {
+                            "type": "array",
+                            "items": { "enum": weekdays }
+                        } })),
+                        tagged("every_n_days", json!({ "days": { "type": "integer", "minimum": 1 } })),
+                        tagged("times_per_week", json!({ "times": {
+                            "type": "integer", "minimum": 1, "maximum": 7
+                        } }))
+                    ] },
+                    "goals": {
+                        "type": "object",
+                        "properties": {
+                            "daily_minutes": minutes,
+                            "weekly_minutes": minutes,
+                            "monthly_minutes": minutes
+                        }
+                    },
+                    "start_date": nullable(&json!({ "$ref": "#/$defs/date" })),
+                    "records": { "type": "array", "items": { "$ref": "#/$defs/record" } }
+                },
+                "required": ["name"]
+            },
+            "record": {
+                "type": "object",
+                "properties": {
+                    "id": id,
+                    "date": { "$ref": "#/$defs/date" },
+                    "end_date": nullable(&json!({ "$ref": "#/$defs/date" })),
+                    "start": nullable(&json!({ "$ref": "#/$defs/time" })),
+                    "end": nullable(&json!({ "$ref": "#/$defs/time" })),
+                    "amount": nullable(&json!({ "type": "number", "minimum": 0 })),
+                    "note": { "type": "string" },
+                    "pomodoro": { "type": "boolean" }
+                },
+                "required": ["date"]
+            }
+        }
+    })
+}
@@ -0,0 +1,178 @@
+use std::io;
+
+use crate::interchange::{Imported, RowError, Source};
+use crate::{new_id, AvalancheError, Date, Record};
+
+/* Exports of other habit trackers. Each record is a day a habit was done,
+ * with an amount for habits that count something.
+ */
+
+/* Checkmarks.csv from a Loop Habit Tracker export: a Date column, then one
+ * column per habit. Yes/no habits have 2 on days they were checked (other
+ * values are unchecked, skipped or implied by the frequency), and numeric
+ * habits have the value with decimals.
+ */
+pub struct LoopHabitTracker;
+
+impl Source for LoopHabitTracker {
+    fn name(&self) -> &'static str {
+        "Loop Habit Tracker"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "Checkmarks.csv"
+    }
+
+    fn read(&self, reader: &mut dyn io::Read) -> Result<Imported, AvalancheError> {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(reader);
+        let mut imported = Imported::default();
+        let headers = reader.headers()?.clone();
+        if !headers
+            .get(0)
+            .is_some_and(|header| header.trim().eq_ignore_ascii_case("date"))
+        {
+            imported.errors.push(RowError {
+                line: 1,
+                message: String::from("The first column should be Date"),
+            });
+            return Ok(imported);
+        }
+
+        for (index, row) in reader.records().enumerate() {
+            let line = index + 2;
+            let row = match row {
+                Ok(row) => row,
+                Err(error) => {
+                    imported.errors.push(RowError {
+                        line,
+                        message: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let date = match parse_date(row.get(0).unwrap_or("")) {
+                Ok(date) => date,
+                Err(message) => {
+                    imported.errors.push(RowError { line, message });
+                    continue;
+                }
+            };
+            // Loop ends the header with a comma, so some columns have no name
+            for (name, value) in headers.iter().zip(row.iter()).skip(1) {
+                let (name, value) = (name.trim(), value.trim());
+                if name.is_empty() {
+                    continue;
+                }
+                let amount = if value.contains('.') {
+                    match value.parse::<f64>() {
+                        Ok(amount) if amount > 0.0 && amount.is_finite() => Some(amount),
+                        Ok(_) => continue,
+                        Err(_) => {
+                            imported.errors.push(RowError {
+                                line,
+                                message: format!("{}: Can't read \"{}\"", name, value),
+                            });
+                            continue;
+                        }
+                    }
+                } else {
+                    match value.parse::<i32>() {
+                        Ok(2) => None,
+                        Ok(_) => continue,
+                        Err(_) => {
+                            imported.errors.push(RowError {
+                                line,
+                                message: format!("{}: Can't read \"{}\"", name, value),
+                            });
+                            continue;
+                        }
+                    }
+                };
+                imported
+                    .records
+                    .push((line, String::from(name), day_record(date.clone(), amount)));
+            }
+        }
+        Ok(imported)
+    }
+}
+
+/* Logs with a date, habit name and value on each line, and maybe a header.
+ * A number is the amount. No value, yes, y, true, done or x means the habit
+ * was done, and 0, no, n or false that it wasn't.
+ */
+pub struct DateHabitValue;
+
+impl Source for DateHabitValue {
+    fn name(&self) -> &'static str {
+        "date,habit,value"
+    }
+
+    fn file_name(&self) -> &'static str {
+        "log.csv"
+    }
+
+    fn read(&self, reader: &mut dyn io::Read) -> Result<Imported, AvalancheError> {
+        let mut reader = ::csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(reader);
+        let mut imported = Imported::default();
+
+        for (index, row) in reader.records().enumerate() {
+            let line = index + 1;
+            let result = row.map_err(|error| error.to_string()).and_then(|row| {
+                let cell = |column| row.get(column).unwrap_or("").trim();
+                let date = match parse_date(cell(0)) {
+                    Ok(date) => date,
+                    // The header
+                    Err(_) if index == 0 => return Ok(None),
+                    Err(message) => return Err(message),
+                };
+                let name = cell(1);
+                if name.is_empty() {
+                    return Err(String::from("No habit name"));
+                }
+                let amount = match cell(2).to_lowercase().as_str() {
+                    "" | "yes" | "y" | "true" | "done" | "x" => None,
+                    "0" | "no" | "n" | "false" => return Ok(None),
+                    value => match value.parse::<f64>() {
+                        Ok(amount) if amount > 0.0 && amount.is_finite() => Some(amount),
+                        Ok(_) => return Err(String::from("Value can't be negative")),
+                        Err(_) => {
+                            return Err(String::from("Value should be a number or yes or no"))
+                        }
+                    },
+                };
+                Ok(Some((String::from(name), day_record(date, amount))))
+            });
+            match result {
+                Ok(Some((name, record))) => imported.records.push((line, name, record)),
+                Ok(None) => (),
+                Err(message) => imported.errors.push(RowError { line, message }),
+            }
+        }
+        Ok(imported)
+    }
+}
+
+fn parse_date(value: &str) -> Result<Date, String> {
+    value
+        .parse::<Date>()
+        .map_err(|error| format!("Date: {}", error))
+}
+
+fn day_record(date: Date, amount: Option<f64>) -> Record {
+    Record {
+        id: new_id(),
+        note: String::new(),
+        date,
+        start_time: None,
+        end_time: None,
+        end_date: None,
+        amount,
+        pomodoro: false,
+    }
+}
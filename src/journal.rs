@@ -0,0 +1,55 @@
+use std::fs;
+use std::io::Write;
+
+use crate::edit::Edit;
+use crate::AvalancheError;
+
+/* Unsaved edits are appended to <data file>.journal, one per line, and the
+ * journal is cleared whenever the data file is saved. If it isn't empty on
+ * the next launch the last session ended without saving.
+ */
+pub struct Journal {
+    pub path: String,
+}
+
+impl Journal {
+    pub fn for_data_file(filename: &str) -> Journal {
+        Journal {
+            path: format!("{}.journal", filename),
+        }
+    }
+
+    pub fn append(&self, edit: &Edit) -> Result<(), AvalancheError> {
+        let mut line = ron::to_string(edit)?;
+        line.push('\n');
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /* Reads the edits in the order they were made. A crash while writing
+     * can leave the last line cut off, so reading stops at the first line
+     * that doesn't parse.
+     */
+    pub fn read(&self) -> Result<Vec<Edit>, AvalancheError> {
+        if !fs::exists(&self.path)? {
+            return Ok(Vec::new());
+        }
+        let contents = fs::read_to_string(&self.path)?;
+        Ok(contents
+            .lines()
+            .map_while(|line| ron::from_str(line).ok())
+            .collect())
+    }
+
+    pub fn clear(&self) -> Result<(), AvalancheError> {
+        if fs::exists(&self.path)? {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
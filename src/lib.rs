@@ -1,14 +1,71 @@
 #![allow(non_snake_case)]
 
+use chrono::{Datelike, Timelike};
 use serde::{Deserialize, Serialize};
-use std::error::Error;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
 use std::fs;
+use std::hash::{BuildHasher, Hasher};
 use std::io::Write;
 use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use edit::Edit;
+pub use error::{AvalancheError, ValidationError};
+pub use timer::Timer;
 
 // Note: Derivations of PartialEq and Debug used in tests
 
-#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+
+    // Days since Monday, so Monday is 0 and Sunday is 6
+    pub fn number_from_monday(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_number_from_monday(number: u8) -> Weekday {
+        Self::ALL[(number % 7) as usize]
+    }
+
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Weekday::Monday => "Mon",
+            Weekday::Tuesday => "Tue",
+            Weekday::Wednesday => "Wed",
+            Weekday::Thursday => "Thu",
+            Weekday::Friday => "Fri",
+            Weekday::Saturday => "Sat",
+            Weekday::Sunday => "Sun",
+        }
+    }
+}
+
+// Field order matters here: the derived Ord compares year, then month, then
+// day, which is chronological order for valid dates.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Deserialize, Serialize)]
 pub struct Date {
     pub year: u16,
     pub month: u8,
@@ -16,11 +73,15 @@ pub struct Date {
 }
 
 impl Date {
-    fn month_length(month: u8, year: u16) -> u8 {
+    pub fn is_leap_year(year: u16) -> bool {
+        (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+    }
+
+    pub fn month_length(month: u8, year: u16) -> u8 {
         match month {
             1 => 31,
-            2 if year % 4 == 0 => 29,
-            2 if year % 4 != 0 => 28,
+            2 if Self::is_leap_year(year) => 29,
+            2 => 28,
             3 => 31,
             4 => 30,
             5 => 31,
@@ -36,41 +97,97 @@ impl Date {
     }
 
     pub fn is_valid(&self) -> bool {
-        if self.day <= Self::month_length(self.month, self.year) && self.day != 0 {
-            return true;
+        self.day <= Self::month_length(self.month, self.year) && self.day != 0
+    }
+
+    /* Days since 1970-01-01 in the proleptic Gregorian calendar. This is the
+     * "days from civil" algorithm: shifting the year to start in March puts
+     * the leap day at the end, so the day of the year can be computed with a
+     * fixed formula. Eras are 400 year cycles of 146097 days.
+     */
+    pub fn to_days(&self) -> i64 {
+        let month = self.month as i64;
+        let year = self.year as i64 - if month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let shifted_month = (month + 9) % 12; // March is 0
+        let day_of_year = (153 * shifted_month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    // Inverse of to_days
+    pub fn from_days(days: i64) -> Date {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
         } else {
-            return false;
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
         }
     }
 
+    pub fn add_days(&self, days: i64) -> Date {
+        Self::from_days(self.to_days() + days)
+    }
+
+    pub fn sub_days(&self, days: i64) -> Date {
+        Self::from_days(self.to_days() - days)
+    }
+
+    // Positive if other is after self
+    pub fn days_until(&self, other: &Date) -> i64 {
+        other.to_days() - self.to_days()
+    }
+
+    pub fn days_between(first: &Date, second: &Date) -> i64 {
+        first.days_until(second).abs()
+    }
+
+    pub fn day_of_week(&self) -> Weekday {
+        // 1970-01-01 was a Thursday
+        Weekday::from_number_from_monday((self.to_days() + 3).rem_euclid(7) as u8)
+    }
+
+    // Day of the year, starting at 1 for January 1st
+    pub fn ordinal(&self) -> u16 {
+        let first = Date {
+            year: self.year,
+            month: 1,
+            day: 1,
+        };
+        (first.days_until(self) + 1) as u16
+    }
+
+    /* ISO 8601 week: weeks start on Monday and week 1 is the week containing
+     * the year's first Thursday. Returns (week-based year, week), since the
+     * first and last few days of a year can belong to a week of the
+     * neighbouring year.
+     */
+    pub fn iso_week(&self) -> (u16, u8) {
+        let thursday = self.add_days(3 - self.day_of_week().number_from_monday() as i64);
+        let week = (thursday.ordinal() - 1) / 7 + 1;
+        (thursday.year, week as u8)
+    }
+
     pub fn is_after(&self, comp: &Date) -> bool {
-        if (self.year == comp.year && self.month == comp.month && self.day > comp.day)
-            || (self.year == comp.year && self.month > comp.month)
-            || (self.year > comp.year)
-        {
-            return true;
-        }
-        false
+        self > comp
     }
 
     pub fn is_day_after(&self, comp: &Date) -> bool {
-        if self.day == comp.day + 1 && self.month == comp.month && self.year == comp.year {
-            return true;
-        } else if comp.day == Self::month_length(comp.month, comp.year)
-            && self.month == (comp.month + 1)
-            && self.day == 1
-            && self.year == comp.year
-        {
-            return true;
-        } else if comp.day == 31
-            && comp.month == 12
-            && self.day == 1
-            && self.month == 1
-            && self.year == comp.year + 1
-        {
-            return true;
-        }
-        false
+        comp.days_until(self) == 1
     }
 }
 
@@ -81,11 +198,11 @@ pub struct Time {
 }
 
 impl Time {
-    pub fn difference(start: &Time, end: &Time) -> Result<Time, Box<dyn Error>> {
+    pub fn difference(start: &Time, end: &Time) -> Result<Time, AvalancheError> {
         let start_minutes: u16 = start.hours * 60 + start.minutes;
         let end_minutes: u16 = end.hours * 60 + end.minutes;
         if end_minutes < start_minutes {
-            return Err("End time before start time".into());
+            return Err(ValidationError::EndBeforeStart.into());
         }
         let difference = end_minutes - start_minutes;
         let difference_hours = difference / 60;
@@ -96,23 +213,99 @@ impl Time {
         })
     }
 
+    // The longest length a Time can hold
+    pub const MAX: Time = Time {
+        hours: u16::MAX,
+        minutes: 59,
+    };
+
+    // None if it's longer than Time::MAX
+    pub fn from_minutes(minutes: u32) -> Option<Time> {
+        Some(Time {
+            hours: u16::try_from(minutes / 60).ok()?,
+            minutes: (minutes % 60) as u16,
+        })
+    }
+
+    // The time of day that many minutes after midnight, wrapping around
+    // past the end of the day
+    pub fn of_day(minutes: u32) -> Time {
+        Time {
+            hours: (minutes % 1440 / 60) as u16,
+            minutes: (minutes % 60) as u16,
+        }
+    }
+
+    pub fn as_minutes(&self) -> u32 {
+        self.hours as u32 * 60 + self.minutes as u32
+    }
+
     pub fn is_valid_time_of_day(&self) -> bool {
-        if self.hours < 24 && self.minutes < 60 {
-            return true;
-        } else {
-            return false;
+        self.hours < 24 && self.minutes < 60
+    }
+}
+
+// Source of the current date and time, so stats can be tested against a
+// fixed day instead of the system clock.
+pub trait Clock {
+    fn today(&self) -> Date;
+    fn now(&self) -> Time;
+    // Seconds since the epoch, for measuring how long things take
+    fn timestamp(&self) -> i64;
+}
+
+// Local time of the system
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn today(&self) -> Date {
+        let today = chrono::Local::now().date_naive();
+        Date {
+            year: today.year() as u16,
+            month: today.month() as u8,
+            day: today.day() as u8,
         }
     }
+
+    fn now(&self) -> Time {
+        let now = chrono::Local::now().time();
+        Time {
+            hours: now.hour() as u16,
+            minutes: now.minute() as u16,
+        }
+    }
+
+    fn timestamp(&self) -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+}
+
+pub struct FixedClock {
+    pub date: Date,
+    pub time: Time,
+}
+
+impl Clock for FixedClock {
+    fn today(&self) -> Date {
+        self.date.clone()
+    }
+
+    fn now(&self) -> Time {
+        self.time.clone()
+    }
+
+    // As if the fixed time were UTC
+    fn timestamp(&self) -> i64 {
+        self.date.to_days() * 86400 + self.time.as_minutes() as i64 * 60
+    }
 }
 
 impl Add for Time {
     type Output = Self;
 
+    // Stops at Time::MAX instead of overflowing
     fn add(self, other: Self) -> Self {
-        Self {
-            hours: self.hours + other.hours + (self.minutes + other.minutes) / 60,
-            minutes: (self.minutes + other.minutes) % 60,
-        }
+        Time::from_minutes(self.as_minutes() + other.as_minutes()).unwrap_or(Time::MAX)
     }
 }
 
@@ -122,83 +315,733 @@ impl AddAssign for Time {
     }
 }
 
+// Dates are written as 2025-01-31 and times as 17:05 in exported files
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = AvalancheError;
+
+    fn from_str(string: &str) -> Result<Date, AvalancheError> {
+        let unreadable = || ValidationError::Unreadable(String::from(string));
+        let parts: Vec<&str> = string.trim().split('-').collect();
+        let [year, month, day] = parts.as_slice() else {
+            return Err(unreadable().into());
+        };
+        let date = Date {
+            year: year.parse().map_err(|_| unreadable())?,
+            month: month.parse().map_err(|_| unreadable())?,
+            day: day.parse().map_err(|_| unreadable())?,
+        };
+        if !date.is_valid() {
+            return Err(ValidationError::InvalidDate(date).into());
+        }
+        Ok(date)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hours, self.minutes)
+    }
+}
+
+// Only reads times of day, not durations
+impl FromStr for Time {
+    type Err = AvalancheError;
+
+    fn from_str(string: &str) -> Result<Time, AvalancheError> {
+        let unreadable = || ValidationError::Unreadable(String::from(string));
+        let (hours, minutes) = string.trim().split_once(':').ok_or_else(unreadable)?;
+        let time = Time {
+            hours: hours.parse().map_err(|_| unreadable())?,
+            minutes: minutes.parse().map_err(|_| unreadable())?,
+        };
+        if !time.is_valid_time_of_day() {
+            return Err(ValidationError::InvalidTime(time).into());
+        }
+        Ok(time)
+    }
+}
+
+/* Which fields are used depends on the kind of habit: timed records have
+ * start and end times, quantity records have an amount. Times are optional
+ * so that records of other kinds don't need made-up values.
+ */
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub struct Record {
+    // 0 until an ID is assigned. See new_id.
+    #[serde(default)]
+    pub id: u64,
     pub note: String,
     pub date: Date,
-    pub start_time: Time,
-    pub end_time: Time,
+    #[serde(default)]
+    pub start_time: Option<Time>,
+    #[serde(default)]
+    pub end_time: Option<Time>,
+    // None means the record ends on the same date it starts
+    #[serde(default)]
+    pub end_date: Option<Date>,
+    #[serde(default)]
+    pub amount: Option<f64>,
+    // Logged by a finished pomodoro work interval
+    #[serde(default)]
+    pub pomodoro: bool,
 }
 
 impl Record {
-    pub fn length(&self) -> Result<Time, Box<dyn Error>> {
-        Time::difference(&self.start_time, &self.end_time)
+    pub fn end_day(&self) -> &Date {
+        self.end_date.as_ref().unwrap_or(&self.date)
+    }
+
+    // Records without both times have no length
+    pub fn length(&self) -> Result<Time, AvalancheError> {
+        let (start_time, end_time) = match (&self.start_time, &self.end_time) {
+            (Some(start_time), Some(end_time)) => (start_time, end_time),
+            _ => return Ok(Time::of_day(0)),
+        };
+        let start_minutes = self.date.to_days() * 1440 + start_time.as_minutes() as i64;
+        let end_minutes = self.end_day().to_days() * 1440 + end_time.as_minutes() as i64;
+        if end_minutes < start_minutes {
+            return Err(ValidationError::EndBeforeStart.into());
+        }
+        u32::try_from(end_minutes - start_minutes)
+            .ok()
+            .and_then(Time::from_minutes)
+            .ok_or_else(|| ValidationError::TooLong.into())
+    }
+
+    // Checks the dates and times exist and that the record doesn't end
+    // before it starts
+    pub fn validate(&self) -> Result<(), AvalancheError> {
+        for date in [Some(&self.date), self.end_date.as_ref()]
+            .into_iter()
+            .flatten()
+        {
+            if !date.is_valid() {
+                return Err(ValidationError::InvalidDate(date.clone()).into());
+            }
+        }
+        for time in [&self.start_time, &self.end_time].into_iter().flatten() {
+            if !time.is_valid_time_of_day() {
+                return Err(ValidationError::InvalidTime(time.clone()).into());
+            }
+        }
+        self.length()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Period {
+    Day,
+    Week, // ISO weeks, starting on Monday, unless another start is given
+    Month,
+}
+
+impl Period {
+    pub fn start_of(&self, date: &Date) -> Date {
+        self.start_with(date, Weekday::Monday)
+    }
+
+    // Like start_of, with weeks starting on week_start
+    pub fn start_with(&self, date: &Date, week_start: Weekday) -> Date {
+        match self {
+            Period::Day => date.clone(),
+            Period::Week => date.sub_days(
+                (date.day_of_week().number_from_monday() + 7 - week_start.number_from_monday())
+                    as i64
+                    % 7,
+            ),
+            Period::Month => Date {
+                year: date.year,
+                month: date.month,
+                day: 1,
+            },
+        }
+    }
+
+    // Start of the period following the one starting on period_start
+    pub fn next_start(&self, period_start: &Date) -> Date {
+        match self {
+            Period::Day => period_start.add_days(1),
+            Period::Week => period_start.add_days(7),
+            Period::Month => period_start
+                .add_days(Date::month_length(period_start.month, period_start.year) as i64),
+        }
+    }
+}
+
+// Duration targets. A habit can have any combination of them.
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
+pub struct Goals {
+    pub daily: Option<Time>,
+    pub weekly: Option<Time>,
+    pub monthly: Option<Time>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GoalProgress {
+    pub target: Time,
+    // Time logged so far in the period containing today
+    pub current: Time,
+    // Counts of finished periods, from the first record up to the current one
+    pub met: u32,
+    pub missed: u32,
+}
+
+impl GoalProgress {
+    pub fn is_met(&self) -> bool {
+        self.current.as_minutes() >= self.target.as_minutes()
+    }
+}
+
+// When a habit is expected to be done. Streaks count consecutive scheduled
+// slots that were done, so days outside the schedule don't break them.
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
+pub enum Schedule {
+    #[default]
+    Daily,
+    Weekdays(Vec<Weekday>),
+    // Once in every block of N days, counted from the first record
+    EveryNDays(u16),
+    // On N different days of each week
+    TimesPerWeek(u8),
+}
+
+// A span of days (inclusive) in which a scheduled habit is due
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScheduledSlot {
+    pub start: Date,
+    pub end: Date,
+    pub done: bool,
+}
+
+impl Schedule {
+    /* Returns the (start, end) of every slot from first through last. For
+     * EveryNDays the blocks are anchored on first, for TimesPerWeek the
+     * first slot is the whole week containing first.
+     */
+    pub fn slots(&self, first: &Date, last: &Date) -> Vec<(Date, Date)> {
+        self.slots_with(first, last, Weekday::Monday)
+    }
+
+    // Like slots, with weeks starting on week_start
+    pub fn slots_with(&self, first: &Date, last: &Date, week_start: Weekday) -> Vec<(Date, Date)> {
+        let mut slots = Vec::new();
+        match self {
+            Schedule::Daily => {
+                let mut date = first.clone();
+                while date <= *last {
+                    slots.push((date.clone(), date.clone()));
+                    date = date.add_days(1);
+                }
+            }
+            Schedule::Weekdays(weekdays) => {
+                let mut date = first.clone();
+                while date <= *last {
+                    if weekdays.contains(&date.day_of_week()) {
+                        slots.push((date.clone(), date.clone()));
+                    }
+                    date = date.add_days(1);
+                }
+            }
+            Schedule::EveryNDays(days) => {
+                let days = (*days).max(1) as i64;
+                let mut start = first.clone();
+                while start <= *last {
+                    let end = start.add_days(days - 1);
+                    slots.push((start, end.clone()));
+                    start = end.add_days(1);
+                }
+            }
+            Schedule::TimesPerWeek(_) => {
+                let mut start = Period::Week.start_with(first, week_start);
+                while start <= *last {
+                    let end = start.add_days(6);
+                    slots.push((start, end.clone()));
+                    start = end.add_days(1);
+                }
+            }
+        }
+        slots
+    }
+
+    // Number of distinct days with records needed to complete a slot
+    pub fn days_per_slot(&self) -> usize {
+        match self {
+            Schedule::TimesPerWeek(times) => (*times).max(1) as usize,
+            _ => 1,
+        }
+    }
+
+    pub fn streak_unit(&self) -> &'static str {
+        match self {
+            Schedule::Daily => "days",
+            Schedule::Weekdays(_) => "scheduled days",
+            Schedule::EveryNDays(_) => "intervals",
+            Schedule::TimesPerWeek(_) => "weeks",
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Schedule::Daily => String::from("Every day"),
+            Schedule::Weekdays(weekdays) => {
+                let names: Vec<&str> = Weekday::ALL
+                    .iter()
+                    .filter(|weekday| weekdays.contains(weekday))
+                    .map(|weekday| weekday.short_name())
+                    .collect();
+                names.join(", ")
+            }
+            Schedule::EveryNDays(days) => format!("Every {} days", days),
+            Schedule::TimesPerWeek(times) => format!("{} times per week", times),
+        }
     }
 }
 
+#[derive(Clone, PartialEq, Debug)]
+pub struct Streak {
+    pub length: u16,
+    // Dates of the first and last record in the streak
+    pub start: Date,
+    pub end: Date,
+}
+
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
+pub enum HabitKind {
+    // Time spent doing something
+    #[default]
+    Timed,
+    // Something to avoid. Records are relapses.
+    Quit,
+    // An amount measured in unit, like pages or glasses
+    Quantity {
+        unit: String,
+    },
+    // Done or not done. Records only have a date and a note.
+    CheckIn,
+}
+
+impl HabitKind {
+    /* Checks the record only has what this kind of habit logs: amounts for
+     * quantity habits, and times for timed habits and relapses. Records
+     * of timed habits and relapses may be missing their times.
+     */
+    pub fn check_record(&self, record: &Record) -> Result<(), ValidationError> {
+        let has_times = record.start_time.is_some() || record.end_time.is_some();
+        let fits = match self {
+            HabitKind::Timed => record.amount.is_none(),
+            HabitKind::Quit => record.amount.is_none() && record.end_time.is_none(),
+            HabitKind::Quantity { .. } => record.amount.is_some() && !has_times,
+            HabitKind::CheckIn => record.amount.is_none() && !has_times,
+        };
+        if !fits {
+            return Err(ValidationError::WrongKind);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct QuantityStats {
+    pub unit: String,
+    pub total: f64,
+    // None if there are no records
+    pub average_per_record: Option<f64>,
+    // Averaged over days with at least one record
+    pub average_per_day: Option<f64>,
+    // Sum of amounts for each day with records, in date order
+    pub per_day: Vec<(Date, f64)>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct QuitStats {
+    // None if there hasn't been a relapse
+    pub days_since_relapse: Option<i64>,
+    // Longest run of days without a relapse, including the current one
+    pub longest_clean_run: i64,
+    pub relapse_count: usize,
+    // None if the tracked period is empty
+    pub relapses_per_week: Option<f32>,
+}
+
 pub struct HabitStats {
     pub streak_length: u16,
+    /* Streak that is still alive today: the slot before today's must have
+     * been done, and today's slot counts once it is done.
+     */
+    pub current_streak: u16,
+    pub longest_streak: Option<Streak>,
+    // Today's slot isn't done yet and there is a current streak to lose
+    pub streak_at_risk: bool,
+    // Fraction of due slots that were done, None if nothing was due yet
+    pub completion_rate: Option<f32>,
     pub total_time: Time,
+    // Records logged by finished pomodoro work intervals
+    pub pomodoros: usize,
+    pub daily_goal: Option<GoalProgress>,
+    pub weekly_goal: Option<GoalProgress>,
+    pub monthly_goal: Option<GoalProgress>,
+    // Only for HabitKind::Quit
+    pub quit: Option<QuitStats>,
+    // Only for HabitKind::Quantity
+    pub quantity: Option<QuantityStats>,
 }
 
-#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[derive(Clone, PartialEq, Debug, Default, Deserialize, Serialize)]
 pub struct Habit {
+    // 0 until an ID is assigned. See new_id.
+    #[serde(default)]
+    pub id: u64,
     pub name: String,
     pub records: Vec<Record>,
+    #[serde(default)]
+    pub goals: Goals,
+    #[serde(default)]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub kind: HabitKind,
+    // Day tracking began. Quit habits count clean days from here.
+    #[serde(default)]
+    pub start_date: Option<Date>,
 }
 
 impl Habit {
-    pub fn get_stats(&self) -> HabitStats {
-        let mut total_time = Time {
-            hours: 0,
-            minutes: 0,
-        };
+    pub fn find_record(&self, id: u64) -> Option<&Record> {
+        self.records.iter().find(|record| record.id == id)
+    }
 
-        // Find most recent date with a record
+    // Assigns an ID to the record if it doesn't have one, and returns it
+    pub fn add_record(&mut self, mut record: Record) -> u64 {
+        if record.id == 0 {
+            record.id = new_id();
+        }
+        let id = record.id;
+        self.records.push(record);
+        id
+    }
 
-        if self.records.is_empty() {
-            return HabitStats {
-                streak_length: 0,
-                total_time: total_time,
-            };
+    // Replaces the record with the same ID. Returns false if there is none.
+    pub fn update_record(&mut self, record: Record) -> bool {
+        match self.records.iter_mut().find(|old| old.id == record.id) {
+            Some(old) => {
+                *old = record;
+                true
+            }
+            None => false,
         }
+    }
 
-        // Start by assuming the most recent date is the latest record
-        // The case of an empty vector was already handled above
+    pub fn remove_record(&mut self, id: u64) -> Option<Record> {
+        let index = self.records.iter().position(|record| record.id == id)?;
+        Some(self.records.remove(index))
+    }
 
-        let mut most_recent_date = self.records.last().unwrap().date.clone();
+    pub fn is_done_on(&self, date: &Date) -> bool {
+        self.records.iter().any(|record| record.date == *date)
+    }
 
-        /* This loop will be used to find the most recent event and the
-         * total time.
-         */
+    /* Adds a record with only a date and a note. Returns false without
+     * adding anything if there is already a record on that date.
+     */
+    pub fn check_in(&mut self, date: Date, note: String) -> bool {
+        if self.is_done_on(&date) {
+            return false;
+        }
+        self.records.push(Record {
+            id: new_id(),
+            note,
+            date,
+            start_time: None,
+            end_time: None,
+            end_date: None,
+            amount: None,
+            pomodoro: false,
+        });
+        true
+    }
+
+    /* Every slot of the schedule from the first record up to today (or the
+     * latest record, if that is later), and whether it was done.
+     */
+    pub fn scheduled_slots(&self, today: &Date) -> Vec<ScheduledSlot> {
+        self.scheduled_slots_with(today, Weekday::Monday)
+    }
+
+    pub fn scheduled_slots_with(&self, today: &Date, week_start: Weekday) -> Vec<ScheduledSlot> {
+        let days: BTreeSet<Date> = self
+            .records
+            .iter()
+            .map(|record| record.date.clone())
+            .collect();
+        let first = match days.first() {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+        let last = days.last().unwrap().max(today);
+
+        let days_per_slot = self.schedule.days_per_slot();
+        self.schedule
+            .slots_with(first, last, week_start)
+            .into_iter()
+            .map(|(start, end)| {
+                let done = days.range(start.clone()..=end.clone()).count() >= days_per_slot;
+                ScheduledSlot { start, end, done }
+            })
+            .collect()
+    }
+
+    pub fn goal_progress(&self, period: Period, target: &Time, today: &Date) -> GoalProgress {
+        self.goal_progress_with(period, target, today, Weekday::Monday)
+    }
 
+    pub fn goal_progress_with(
+        &self,
+        period: Period,
+        target: &Time,
+        today: &Date,
+        week_start: Weekday,
+    ) -> GoalProgress {
+        // Minutes logged per period, keyed by the start date of the period
+        let mut logged: BTreeMap<Date, u32> = BTreeMap::new();
         for record in &self.records {
-            total_time += record.length().unwrap();
-            if !most_recent_date.is_after(&record.date) {
-                most_recent_date = record.date.clone();
+            if let Ok(length) = record.length() {
+                let minutes = logged
+                    .entry(period.start_with(&record.date, week_start))
+                    .or_insert(0);
+                *minutes = minutes.saturating_add(length.as_minutes());
             }
         }
 
-        // Now check for the streak
+        let current_start = period.start_with(today, week_start);
+        let current = Time::from_minutes(logged.get(&current_start).copied().unwrap_or(0))
+            .unwrap_or(Time::MAX);
 
-        let mut comp_date = most_recent_date;
-        let mut streak_len: u16 = 0;
-        let mut day_before_found = true;
-        while day_before_found {
-            streak_len += 1;
-            for record in &self.records {
-                if comp_date.is_day_after(&record.date) {
-                    day_before_found = true;
-                    comp_date = record.date.clone();
-                    break;
+        let mut met = 0;
+        let mut missed = 0;
+        if let Some(first_start) = logged.keys().next() {
+            let mut period_start = first_start.clone();
+            while period_start < current_start {
+                if logged.get(&period_start).copied().unwrap_or(0) >= target.as_minutes() {
+                    met += 1;
+                } else {
+                    missed += 1;
                 }
-                day_before_found = false;
+                period_start = period.next_start(&period_start);
             }
         }
 
+        GoalProgress {
+            target: target.clone(),
+            current,
+            met,
+            missed,
+        }
+    }
+
+    fn longest_streak(&self, slots: &[ScheduledSlot]) -> Option<Streak> {
+        let mut longest: Option<(usize, usize)> = None; // Indices of first and last slot
+        let mut run_start = 0;
+        for (i, slot) in slots.iter().enumerate() {
+            if !slot.done {
+                run_start = i + 1;
+                continue;
+            }
+            // Ties go to the most recent streak
+            match longest {
+                Some((first, last)) if last - first > i - run_start => {}
+                _ => longest = Some((run_start, i)),
+            }
+        }
+
+        let (first, last) = longest?;
+        let days_in = |slot: &ScheduledSlot| {
+            self.records
+                .iter()
+                .map(|record| &record.date)
+                .filter(|date| slot.start <= **date && **date <= slot.end)
+                .cloned()
+                .collect::<BTreeSet<Date>>()
+        };
+        Some(Streak {
+            length: (last - first + 1) as u16,
+            start: days_in(&slots[first]).first()?.clone(),
+            end: days_in(&slots[last]).last()?.clone(),
+        })
+    }
+
+    pub fn get_stats(&self, clock: &dyn Clock) -> HabitStats {
+        self.get_stats_with(clock, Weekday::Monday)
+    }
+
+    // Like get_stats, with weekly goals and schedules starting on week_start
+    pub fn get_stats_with(&self, clock: &dyn Clock, week_start: Weekday) -> HabitStats {
+        let today = &clock.today();
+
+        let daily_goal = self
+            .goals
+            .daily
+            .as_ref()
+            .map(|target| self.goal_progress_with(Period::Day, target, today, week_start));
+        let weekly_goal = self
+            .goals
+            .weekly
+            .as_ref()
+            .map(|target| self.goal_progress_with(Period::Week, target, today, week_start));
+        let monthly_goal = self
+            .goals
+            .monthly
+            .as_ref()
+            .map(|target| self.goal_progress_with(Period::Month, target, today, week_start));
+
+        let mut total_time = Time {
+            hours: 0,
+            minutes: 0,
+        };
+        for record in &self.records {
+            // Records are attributed to the date they start on, so a session
+            // past midnight counts towards the day it began.
+            if let Ok(length) = record.length() {
+                total_time += length;
+            }
+        }
+
+        // The most recent streak ends at the last slot that was done
+        let slots = self.scheduled_slots_with(today, week_start);
+        let streak_length = slots
+            .iter()
+            .rev()
+            .skip_while(|slot| !slot.done)
+            .take_while(|slot| slot.done)
+            .count() as u16;
+
+        // A slot that hasn't ended yet only counts once it is done
+        let due: Vec<&ScheduledSlot> = slots
+            .iter()
+            .filter(|slot| slot.end < *today || slot.done)
+            .collect();
+        let completion_rate = if due.is_empty() {
+            None
+        } else {
+            Some(due.iter().filter(|slot| slot.done).count() as f32 / due.len() as f32)
+        };
+
+        let closed_run = slots
+            .iter()
+            .rev()
+            .filter(|slot| slot.end < *today)
+            .take_while(|slot| slot.done)
+            .count() as u16;
+        let today_slot = slots
+            .iter()
+            .find(|slot| slot.start <= *today && *today <= slot.end);
+        let today_done = today_slot.is_some_and(|slot| slot.done);
+        let current_streak = closed_run + if today_done { 1 } else { 0 };
+        let streak_at_risk = today_slot.is_some() && !today_done && closed_run > 0;
+
         HabitStats {
-            streak_length: streak_len,
-            total_time: total_time,
+            streak_length,
+            current_streak,
+            longest_streak: self.longest_streak(&slots),
+            streak_at_risk,
+            completion_rate,
+            total_time,
+            pomodoros: self.records.iter().filter(|record| record.pomodoro).count(),
+            daily_goal,
+            weekly_goal,
+            monthly_goal,
+            quit: match self.kind {
+                HabitKind::Quit => Some(self.quit_stats(today)),
+                _ => None,
+            },
+            quantity: match &self.kind {
+                HabitKind::Quantity { unit } => Some(self.quantity_stats(unit)),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn quantity_stats(&self, unit: &str) -> QuantityStats {
+        let mut per_day: BTreeMap<Date, f64> = BTreeMap::new();
+        let mut total = 0.0;
+        let mut count = 0;
+        for record in &self.records {
+            if let Some(amount) = record.amount {
+                *per_day.entry(record.date.clone()).or_insert(0.0) += amount;
+                total += amount;
+                count += 1;
+            }
+        }
+
+        QuantityStats {
+            unit: String::from(unit),
+            total,
+            average_per_record: if count > 0 {
+                Some(total / count as f64)
+            } else {
+                None
+            },
+            average_per_day: if per_day.is_empty() {
+                None
+            } else {
+                Some(total / per_day.len() as f64)
+            },
+            per_day: per_day.into_iter().collect(),
+        }
+    }
+
+    pub fn quit_stats(&self, today: &Date) -> QuitStats {
+        let relapses: BTreeSet<Date> = self
+            .records
+            .iter()
+            .map(|record| record.date.clone())
+            .filter(|date| date <= today)
+            .collect();
+        let relapse_count = self.records.iter().filter(|r| r.date <= *today).count();
+
+        // Tracking starts at start_date, or at the first relapse without one
+        let tracking_start = match (&self.start_date, relapses.first()) {
+            (Some(start_date), Some(first)) => start_date.min(first).clone(),
+            (Some(start_date), None) => start_date.clone(),
+            (None, Some(first)) => first.clone(),
+            (None, None) => today.clone(),
+        };
+
+        /* Clean runs are the days strictly between two relapses, plus the days
+         * before the first one and the days since the last one. Today counts
+         * towards the current run.
+         */
+        let mut longest_clean_run = 0;
+        let mut previous: Option<&Date> = None;
+        for relapse in &relapses {
+            let run = match previous {
+                Some(previous) => previous.days_until(relapse) - 1,
+                None => tracking_start.days_until(relapse),
+            };
+            longest_clean_run = longest_clean_run.max(run);
+            previous = Some(relapse);
+        }
+        let days_since_relapse = relapses.last().map(|last| last.days_until(today));
+        let current_run = days_since_relapse.unwrap_or(tracking_start.days_until(today) + 1);
+        longest_clean_run = longest_clean_run.max(current_run);
+
+        let tracked_days = tracking_start.days_until(today) + 1;
+        let relapses_per_week = if tracked_days > 0 {
+            Some(relapse_count as f32 * 7.0 / tracked_days as f32)
+        } else {
+            None
+        };
+
+        QuitStats {
+            days_since_relapse,
+            longest_clean_run,
+            relapse_count,
+            relapses_per_week,
         }
     }
 }
@@ -207,44 +1050,154 @@ impl Habit {
 pub struct UserData {
     pub version: u16,
     pub habits: Vec<Habit>,
+    #[serde(default)]
+    pub timer: Option<Timer>,
+}
+
+impl Default for UserData {
+    fn default() -> Self {
+        UserData {
+            version: UserData::CURRENT_VERSION,
+            habits: Vec::new(),
+            timer: None,
+        }
+    }
+}
+
+/* IDs are random rather than counted up, so records added to two copies of
+ * the same data file don't end up with the same ID. 0 is never returned
+ * since it marks a missing ID.
+ */
+pub fn new_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, AtomicOrdering::Relaxed));
+    if let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(elapsed.as_nanos());
+    }
+    hasher.finish().max(1)
 }
 
 impl UserData {
-    pub fn find_habit_by_name(&self, name: &str) -> Option<usize> {
-        for (i, habit) in self.habits.iter().enumerate() {
-            if habit.name == name {
-                return Some(i);
+    pub fn find_habit_by_name(&self, name: &str) -> Option<u64> {
+        self.habits
+            .iter()
+            .find(|habit| habit.name == name)
+            .map(|habit| habit.id)
+    }
+
+    pub fn find_habit(&self, id: u64) -> Option<&Habit> {
+        self.habits.iter().find(|habit| habit.id == id)
+    }
+
+    pub fn find_habit_mut(&mut self, id: u64) -> Option<&mut Habit> {
+        self.habits.iter_mut().find(|habit| habit.id == id)
+    }
+
+    // Assigns an ID to the habit if it doesn't have one, and returns it
+    pub fn add_habit(&mut self, mut habit: Habit) -> u64 {
+        if habit.id == 0 {
+            habit.id = new_id();
+        }
+        let id = habit.id;
+        self.habits.push(habit);
+        id
+    }
+
+    pub fn remove_habit(&mut self, id: u64) -> Option<Habit> {
+        let index = self.habits.iter().position(|habit| habit.id == id)?;
+        Some(self.habits.remove(index))
+    }
+
+    // Gives IDs to habits and records that don't have one yet
+    pub fn assign_missing_ids(&mut self) {
+        for habit in &mut self.habits {
+            if habit.id == 0 {
+                habit.id = new_id();
+            }
+            for record in &mut habit.records {
+                if record.id == 0 {
+                    record.id = new_id();
+                }
             }
         }
-        None
     }
 
-    pub fn write_to_file(&self, filename: &String) -> Result<(), Box<dyn Error>> {
-        let mut file = fs::File::create(filename)?;
+    /* Writes to a temporary file next to filename and renames it over the
+     * old one, so a failed save leaves the previous file in place.
+     */
+    pub fn write_to_file(&self, filename: &str) -> Result<(), AvalancheError> {
         let file_contents = ron::to_string(&self)?;
-        file.write(file_contents.as_bytes())?;
+        let temp_filename = format!("{}.tmp", filename);
+        let mut file = fs::File::create(&temp_filename)?;
+        file.write_all(file_contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_filename, filename)?;
         Ok(())
     }
 
-    pub fn read_from_file(filename: &String) -> Result<UserData, Box<dyn Error>> {
-        let file = fs::File::open(filename)?;
-        let data: UserData = ron::de::from_reader(file)?;
-        return Ok(data);
+    // Applies edits in order and returns how many no longer applied
+    pub fn replay(&mut self, edits: &[Edit]) -> usize {
+        edits
+            .iter()
+            .filter(|edit| (*edit).clone().apply(self).is_none())
+            .count()
     }
 
-    pub fn try_from_file(filename: &String) -> UserData {
-        if fs::exists(&filename).unwrap() {
-            return Self::read_from_file(filename).unwrap();
-        } else {
-            return UserData {
-                version: UserData::CURRENT_VERSION,
-                habits: Vec::new(),
-            };
+    pub fn record_count(&self) -> usize {
+        self.habits.iter().map(|habit| habit.records.len()).sum()
+    }
+
+    pub fn read_from_file(filename: &str) -> Result<UserData, AvalancheError> {
+        let contents = fs::read_to_string(filename)?;
+        let (data, _) = Self::parse(&contents)?;
+        Ok(data)
+    }
+
+    // Returns the data migrated to the current version along with the
+    // version it was stored as
+    fn parse(contents: &str) -> Result<(UserData, u16), AvalancheError> {
+        let data = migrations::read(contents)?;
+        let version = data.version();
+        let (mut data, _) = migrations::migrate(data);
+        // Records added by hand could still be missing an ID
+        data.assign_missing_ids();
+        Ok((data, version))
+    }
+
+    /* Opens the data file, or starts with no habits if there isn't one yet.
+     * Files from older versions are copied aside before the first save
+     * overwrites them.
+     */
+    pub fn try_from_file(filename: &str) -> Result<UserData, AvalancheError> {
+        if !fs::exists(filename)? {
+            return Ok(UserData::default());
+        }
+        let contents = fs::read_to_string(filename)?;
+        let (data, version) = Self::parse(&contents)?;
+        if version < UserData::CURRENT_VERSION {
+            let backup = migrations::backup_path(filename, version);
+            if !fs::exists(&backup)? {
+                fs::copy(filename, &backup)?;
+            }
         }
+        Ok(data)
     }
 
-    pub const CURRENT_VERSION: u16 = 1;
+    pub const CURRENT_VERSION: u16 = 2;
 }
 
+pub mod backups;
+pub mod config;
+pub mod edit;
+pub mod error;
+pub mod history;
+pub mod interchange;
+pub mod journal;
+pub mod lock;
+pub mod migrations;
+pub mod pomodoro;
+pub mod timer;
+
 #[cfg(test)]
 mod lib_tests;
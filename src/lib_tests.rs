@@ -10,10 +10,10 @@ fn date_is_valid_test() {
     assert!(date1.is_valid());
 
     let date2 = Date {
-        year: 100,
+        year: 2000,
         month: 2,
         day: 29,
-    }; // Valid because of leap year
+    }; // Valid because of leap year (divisible by 400)
     assert!(date2.is_valid());
 
     let date3 = Date {
@@ -43,6 +43,20 @@ fn date_is_valid_test() {
         day: 0,
     }; // Not valid
     assert!(!date6.is_valid());
+
+    let date7 = Date {
+        year: 1900,
+        month: 2,
+        day: 29,
+    }; // Not valid because centuries are only leap years if divisible by 400
+    assert!(!date7.is_valid());
+
+    let date8 = Date {
+        year: 2100,
+        month: 2,
+        day: 29,
+    }; // Not valid
+    assert!(!date8.is_valid());
 }
 
 #[test]
@@ -115,6 +129,157 @@ fn is_day_after_test() {
     assert!(!date1.is_day_after(&date3));
 }
 
+#[test]
+fn date_ordering_test() {
+    let mut dates = vec![
+        Date {
+            year: 2000,
+            month: 1,
+            day: 1,
+        },
+        Date {
+            year: 1999,
+            month: 12,
+            day: 31,
+        },
+        Date {
+            year: 1999,
+            month: 2,
+            day: 28,
+        },
+    ];
+    dates.sort();
+
+    assert_eq!(
+        dates,
+        vec![
+            Date {
+                year: 1999,
+                month: 2,
+                day: 28,
+            },
+            Date {
+                year: 1999,
+                month: 12,
+                day: 31,
+            },
+            Date {
+                year: 2000,
+                month: 1,
+                day: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn date_arithmetic_test() {
+    let date1 = Date {
+        year: 1970,
+        month: 1,
+        day: 1,
+    };
+    let date2 = Date {
+        year: 2024,
+        month: 2,
+        day: 28,
+    };
+    let date3 = Date {
+        year: 1900,
+        month: 2,
+        day: 28,
+    };
+
+    assert_eq!(date1.to_days(), 0);
+    assert_eq!(Date::from_days(0), date1);
+    assert_eq!(Date::from_days(date2.to_days()), date2);
+
+    assert_eq!(
+        date2.add_days(1),
+        Date {
+            year: 2024,
+            month: 2,
+            day: 29,
+        }
+    );
+    assert_eq!(
+        date3.add_days(1),
+        Date {
+            year: 1900,
+            month: 3,
+            day: 1,
+        }
+    );
+    assert_eq!(
+        date1.sub_days(1),
+        Date {
+            year: 1969,
+            month: 12,
+            day: 31,
+        }
+    );
+    assert_eq!(date2.add_days(366).sub_days(366), date2);
+
+    assert_eq!(date1.days_until(&date2), 19781);
+    assert_eq!(date2.days_until(&date1), -19781);
+    assert_eq!(Date::days_between(&date2, &date1), 19781);
+}
+
+#[test]
+fn day_of_week_test() {
+    let date1 = Date {
+        year: 1970,
+        month: 1,
+        day: 1,
+    };
+    let date2 = Date {
+        year: 2025,
+        month: 1,
+        day: 1,
+    };
+    let date3 = Date {
+        year: 2000,
+        month: 2,
+        day: 29,
+    };
+
+    assert_eq!(date1.day_of_week(), Weekday::Thursday);
+    assert_eq!(date2.day_of_week(), Weekday::Wednesday);
+    assert_eq!(date3.day_of_week(), Weekday::Tuesday);
+}
+
+#[test]
+fn ordinal_and_iso_week_test() {
+    let date1 = Date {
+        year: 2024,
+        month: 12,
+        day: 31,
+    };
+    let date2 = Date {
+        year: 2021,
+        month: 1,
+        day: 3,
+    };
+    let date3 = Date {
+        year: 2020,
+        month: 12,
+        day: 31,
+    };
+    let date4 = Date {
+        year: 2025,
+        month: 6,
+        day: 15,
+    };
+
+    assert_eq!(date1.ordinal(), 366);
+    assert_eq!(date2.ordinal(), 3);
+
+    assert_eq!(date1.iso_week(), (2025, 1)); // Belongs to the next year
+    assert_eq!(date2.iso_week(), (2020, 53)); // Belongs to the previous year
+    assert_eq!(date3.iso_week(), (2020, 53));
+    assert_eq!(date4.iso_week(), (2025, 24));
+}
+
 #[test]
 fn time_is_valid_test() {
     let time1 = Time {
@@ -180,81 +345,2153 @@ fn time_add_test() {
             minutes: 30,
         }
     );
+    // Sums stop at the longest time there is
+    assert_eq!(Time::MAX + Time::of_day(1), Time::MAX);
 }
 
 #[test]
-fn write_to_file_test() -> Result<(), Box<dyn Error>> {
-    let record = Record {
-        /* This test passes if the write is successful. It doesn't check what
-         * is written. The output in test.ron should be checked manually.
-         */
-        note: String::from("Writing test functions"),
+fn time_from_minutes_test() {
+    assert_eq!(
+        Time::from_minutes(u16::MAX as u32 * 60 + 59),
+        Some(Time::MAX)
+    );
+    assert_eq!(Time::from_minutes(u16::MAX as u32 * 60 + 60), None);
+    assert_eq!(Time::from_minutes(u32::MAX), None);
+    assert_eq!(Time::of_day(1440 + 65), "01:05".parse().unwrap());
+
+    // Records too long to measure fail instead of wrapping around
+    let date = Date {
+        year: 2000,
+        month: 1,
+        day: 1,
+    };
+    let mut record = Record {
+        end_date: Some(date.add_days(2730)),
+        ..timed_record(date.clone(), 0, 0)
+    };
+    assert_eq!(record.length().unwrap().hours, 2730 * 24);
+    record.end_date = Some(date.add_days(2731));
+    assert!(matches!(
+        record.validate(),
+        Err(AvalancheError::Validation(ValidationError::TooLong))
+    ));
+}
+
+fn clock_on(date: Date) -> FixedClock {
+    FixedClock {
+        date,
+        time: Time {
+            hours: 12,
+            minutes: 0,
+        },
+    }
+}
+
+#[test]
+fn record_length_test() {
+    let same_day = Record {
+        id: 0,
+        note: String::new(),
         date: Date {
             year: 2025,
             month: 1,
             day: 1,
         },
-        start_time: Time {
-            hours: 17,
-            minutes: 0,
-        },
-        end_time: Time {
-            hours: 17,
-            minutes: 20,
+        start_time: Some(Time {
+            hours: 9,
+            minutes: 30,
+        }),
+        end_time: Some(Time {
+            hours: 10,
+            minutes: 15,
+        }),
+        end_date: None,
+        amount: None,
+        pomodoro: false,
+    };
+    assert_eq!(
+        same_day.length().unwrap(),
+        Time {
+            hours: 0,
+            minutes: 45,
+        }
+    );
+
+    let overnight = Record {
+        id: 0,
+        note: String::new(),
+        date: Date {
+            year: 2024,
+            month: 12,
+            day: 31,
         },
+        start_time: Some(Time {
+            hours: 23,
+            minutes: 0,
+        }),
+        end_time: Some(Time {
+            hours: 1,
+            minutes: 0,
+        }),
+        end_date: Some(Date {
+            year: 2025,
+            month: 1,
+            day: 1,
+        }),
+        amount: None,
+        pomodoro: false,
     };
-    let records = vec![record];
+    assert_eq!(
+        overnight.length().unwrap(),
+        Time {
+            hours: 2,
+            minutes: 0,
+        }
+    );
 
-    let habit = Habit {
-        name: String::from("Testing"),
-        records: records,
+    let fast = Record {
+        end_time: Some(Time {
+            hours: 11,
+            minutes: 0,
+        }),
+        end_date: Some(Date {
+            year: 2025,
+            month: 1,
+            day: 2,
+        }),
+        ..overnight.clone()
     };
-    let habits = vec![habit];
+    assert_eq!(
+        fast.length().unwrap(),
+        Time {
+            hours: 36,
+            minutes: 0,
+        }
+    );
 
-    let app_data = UserData {
-        version: UserData::CURRENT_VERSION,
-        habits: habits,
+    let backwards = Record {
+        end_date: None,
+        ..overnight
     };
+    assert!(backwards.length().is_err());
+}
 
-    app_data.write_to_file("test.ron")
+#[test]
+fn get_stats_overnight_test() {
+    let date1 = Date {
+        year: 2025,
+        month: 3,
+        day: 1,
+    };
+    let date2 = Date {
+        year: 2025,
+        month: 3,
+        day: 2,
+    };
+    let records = vec![
+        Record {
+            id: 0,
+            note: String::new(),
+            date: date1.clone(),
+            start_time: Some(Time {
+                hours: 23,
+                minutes: 0,
+            }),
+            end_time: Some(Time {
+                hours: 1,
+                minutes: 0,
+            }),
+            end_date: Some(date2.clone()),
+            amount: None,
+            pomodoro: false,
+        },
+        Record {
+            id: 0,
+            note: String::new(),
+            date: date2,
+            start_time: Some(Time {
+                hours: 22,
+                minutes: 0,
+            }),
+            end_time: Some(Time {
+                hours: 22,
+                minutes: 30,
+            }),
+            end_date: None,
+            amount: None,
+            pomodoro: false,
+        },
+        Record {
+            // Invalid: ends before it starts. Ignored instead of panicking.
+            id: 0,
+            note: String::new(),
+            date: date1,
+            start_time: Some(Time {
+                hours: 12,
+                minutes: 0,
+            }),
+            end_time: Some(Time {
+                hours: 11,
+                minutes: 0,
+            }),
+            end_date: None,
+            amount: None,
+            pomodoro: false,
+        },
+    ];
+    let habit = Habit {
+        name: String::from("Reading"),
+        records,
+        ..Default::default()
+    };
+
+    let stats = habit.get_stats(&clock_on(Date {
+        year: 2025,
+        month: 3,
+        day: 2,
+    }));
+    assert_eq!(stats.streak_length, 2);
+    assert_eq!(
+        stats.total_time,
+        Time {
+            hours: 2,
+            minutes: 30,
+        }
+    );
+}
+
+fn timed_record(date: Date, start_hours: u16, end_hours: u16) -> Record {
+    Record {
+        id: 0,
+        note: String::new(),
+        date,
+        start_time: Some(Time {
+            hours: start_hours,
+            minutes: 0,
+        }),
+        end_time: Some(Time {
+            hours: end_hours,
+            minutes: 0,
+        }),
+        end_date: None,
+        amount: None,
+        pomodoro: false,
+    }
 }
 
 #[test]
-fn read_from_file_test() {
-    /* This test can fail if run concurrently with write_from_file_test.
-     * If it fails, try running with --test-threads 1.
-     */
+fn period_test() {
+    let date = Date {
+        year: 2025,
+        month: 1,
+        day: 15,
+    }; // A Wednesday
 
-    let record = Record {
-        note: String::from("Writing test functions"),
-        date: Date {
+    assert_eq!(Period::Day.start_of(&date), date);
+    assert_eq!(
+        Period::Week.start_of(&date),
+        Date {
             year: 2025,
             month: 1,
+            day: 13,
+        }
+    );
+    assert_eq!(
+        Period::Month.next_start(&Period::Month.start_of(&date)),
+        Date {
+            year: 2025,
+            month: 2,
             day: 1,
+        }
+    );
+}
+
+#[test]
+fn goal_progress_test() {
+    let monday = Date {
+        year: 2025,
+        month: 1,
+        day: 6,
+    };
+    let records = vec![
+        timed_record(monday.clone(), 8, 9),       // Met
+        timed_record(monday.add_days(2), 8, 8),   // Missed (zero length)
+        timed_record(monday.add_days(7), 8, 10),  // Met, next week
+        timed_record(monday.add_days(8), 20, 21), // Today
+        timed_record(monday.add_days(8), 22, 22), // Today, zero length
+    ];
+    let habit = Habit {
+        name: String::from("Running"),
+        records,
+        goals: Goals {
+            daily: Some(Time {
+                hours: 1,
+                minutes: 0,
+            }),
+            weekly: Some(Time {
+                hours: 3,
+                minutes: 0,
+            }),
+            monthly: None,
         },
-        start_time: Time {
-            hours: 17,
+        ..Default::default()
+    };
+
+    let stats = habit.get_stats(&clock_on(monday.add_days(8)));
+
+    let daily = stats.daily_goal.unwrap();
+    assert!(daily.is_met());
+    assert_eq!(daily.met, 2);
+    assert_eq!(daily.missed, 6); // Days without records count as missed
+
+    let weekly = stats.weekly_goal.unwrap();
+    assert!(weekly.is_met());
+    assert_eq!(
+        weekly.current,
+        Time {
+            hours: 3,
             minutes: 0,
-        },
-        end_time: Time {
-            hours: 17,
-            minutes: 20,
-        },
+        }
+    );
+    assert_eq!(weekly.met, 0);
+    assert_eq!(weekly.missed, 1);
+
+    assert!(stats.monthly_goal.is_none());
+}
+
+#[test]
+fn daily_schedule_streak_test() {
+    let date = Date {
+        year: 1999,
+        month: 12,
+        day: 30,
     };
-    let records = vec![record];
+    let habit = Habit {
+        name: String::from("Stretching"),
+        records: vec![
+            timed_record(date.clone(), 8, 9),
+            timed_record(date.add_days(2), 8, 9), // Crosses the new year
+            timed_record(date.add_days(3), 8, 9),
+        ],
+        ..Default::default()
+    };
+
+    let stats = habit.get_stats(&clock_on(date.add_days(3)));
+    assert_eq!(stats.streak_length, 2);
+    assert_eq!(stats.completion_rate, Some(0.75));
+}
+
+#[test]
+fn weekday_schedule_streak_test() {
+    let monday = Date {
+        year: 2025,
+        month: 3,
+        day: 3,
+    };
+    let mut habit = Habit {
+        name: String::from("Gym"),
+        records: vec![
+            timed_record(monday.clone(), 18, 19),
+            timed_record(monday.add_days(2), 18, 19),
+            timed_record(monday.add_days(4), 18, 19),
+            timed_record(monday.add_days(7), 18, 19),
+        ],
+        schedule: Schedule::Weekdays(vec![Weekday::Monday, Weekday::Wednesday, Weekday::Friday]),
+        ..Default::default()
+    };
+
+    // Tuesday: Monday, Wednesday, Friday and Monday were all done
+    let stats = habit.get_stats(&clock_on(monday.add_days(8)));
+    assert_eq!(stats.streak_length, 4);
+    assert_eq!(stats.completion_rate, Some(1.0));
+
+    // The same records every day would be a streak of 1
+    habit.schedule = Schedule::Daily;
+    assert_eq!(
+        habit.get_stats(&clock_on(monday.add_days(8))).streak_length,
+        1
+    );
+}
+
+#[test]
+fn every_n_days_schedule_test() {
+    let date = Date {
+        year: 2025,
+        month: 5,
+        day: 1,
+    };
+    let habit = Habit {
+        name: String::from("Water plants"),
+        records: vec![
+            timed_record(date.clone(), 8, 9),
+            timed_record(date.add_days(5), 8, 9),
+            timed_record(date.add_days(6), 8, 9),
+            timed_record(date.add_days(12), 8, 9), // Slot 6-8 missed
+        ],
+        schedule: Schedule::EveryNDays(3),
+        ..Default::default()
+    };
+
+    let stats = habit.get_stats(&clock_on(date.add_days(12)));
+    assert_eq!(stats.streak_length, 1);
+    // Slots start on days 0, 3, 6, 9 and 12. Day 9 was missed.
+    assert_eq!(stats.completion_rate, Some(0.8));
+}
 
+#[test]
+fn times_per_week_schedule_test() {
+    let monday = Date {
+        year: 2025,
+        month: 3,
+        day: 3,
+    };
     let habit = Habit {
-        name: String::from("Testing"),
-        records: records,
+        name: String::from("Swimming"),
+        records: vec![
+            timed_record(monday.clone(), 7, 8),
+            timed_record(monday.add_days(3), 7, 8),
+            timed_record(monday.add_days(9), 7, 8),
+            timed_record(monday.add_days(9), 19, 20), // Same day only counts once
+            timed_record(monday.add_days(13), 7, 8),
+            timed_record(monday.add_days(14), 7, 8), // Current week, not done yet
+        ],
+        schedule: Schedule::TimesPerWeek(2),
+        ..Default::default()
     };
-    let habits = vec![habit];
 
-    let reference_app_data = UserData {
-        version: UserData::CURRENT_VERSION,
-        habits: habits,
+    let stats = habit.get_stats(&clock_on(monday.add_days(15)));
+    assert_eq!(stats.streak_length, 2);
+    assert_eq!(stats.completion_rate, Some(1.0));
+}
+
+#[test]
+fn current_streak_test() {
+    let date = Date {
+        year: 2025,
+        month: 2,
+        day: 10,
+    };
+    let habit = Habit {
+        name: String::from("Meditation"),
+        records: vec![
+            timed_record(date.clone(), 7, 8),
+            timed_record(date.add_days(1), 7, 8),
+            timed_record(date.add_days(2), 7, 8),
+        ],
+        ..Default::default()
     };
 
-    let file_app_data = UserData::read_from_file("test.ron")
-        .expect("Failed to read data file. Make sure to run tests with 1 thread.\n");
-    assert_eq!(reference_app_data, file_app_data);
+    // Done today
+    let stats = habit.get_stats(&clock_on(date.add_days(2)));
+    assert_eq!(stats.current_streak, 3);
+    assert!(!stats.streak_at_risk);
+
+    // Not done yet today, but the streak is still alive
+    let stats = habit.get_stats(&clock_on(date.add_days(3)));
+    assert_eq!(stats.current_streak, 3);
+    assert!(stats.streak_at_risk);
+
+    // Nothing yesterday or today: broken, even though it was the most recent
+    let stats = habit.get_stats(&clock_on(date.add_days(700)));
+    assert_eq!(stats.current_streak, 0);
+    assert!(!stats.streak_at_risk);
+    assert_eq!(stats.streak_length, 3);
+}
+
+#[test]
+fn longest_streak_test() {
+    let date = Date {
+        year: 2024,
+        month: 2,
+        day: 27,
+    };
+    let habit = Habit {
+        name: String::from("Journaling"),
+        records: vec![
+            timed_record(date.clone(), 21, 22),
+            timed_record(date.add_days(1), 21, 22),
+            timed_record(date.add_days(2), 21, 22),
+            timed_record(date.add_days(3), 21, 22),
+            timed_record(date.add_days(10), 21, 22),
+            timed_record(date.add_days(11), 21, 22),
+        ],
+        ..Default::default()
+    };
+
+    let stats = habit.get_stats(&clock_on(date.add_days(11)));
+    assert_eq!(stats.current_streak, 2);
+    assert_eq!(
+        stats.longest_streak,
+        Some(Streak {
+            length: 4,
+            start: date.clone(),
+            end: Date {
+                year: 2024,
+                month: 3,
+                day: 1,
+            },
+        })
+    );
+
+    let empty = Habit::default();
+    assert!(empty.get_stats(&clock_on(date)).longest_streak.is_none());
+}
+
+#[test]
+fn quit_stats_test() {
+    let date = Date {
+        year: 2025,
+        month: 4,
+        day: 1,
+    };
+    let mut habit = Habit {
+        name: String::from("Smoking"),
+        records: vec![
+            timed_record(date.clone(), 9, 9),
+            timed_record(date.add_days(5), 9, 9),
+            timed_record(date.add_days(6), 9, 9),
+        ],
+        kind: HabitKind::Quit,
+        start_date: Some(date.sub_days(3)),
+        ..Default::default()
+    };
+
+    let quit = habit.get_stats(&clock_on(date.add_days(10))).quit.unwrap();
+    assert_eq!(quit.days_since_relapse, Some(4));
+    assert_eq!(quit.longest_clean_run, 4);
+    assert_eq!(quit.relapse_count, 3);
+    assert_eq!(quit.relapses_per_week, Some(1.5));
+
+    // Without relapses, every day since the start is clean
+    habit.records.clear();
+    let quit = habit.get_stats(&clock_on(date.add_days(6))).quit.unwrap();
+    assert_eq!(quit.days_since_relapse, None);
+    assert_eq!(quit.longest_clean_run, 10);
+    assert_eq!(quit.relapses_per_week, Some(0.0));
+
+    // Timed habits don't get quit stats
+    habit.kind = HabitKind::Timed;
+    assert!(habit.get_stats(&clock_on(date)).quit.is_none());
+}
+
+#[test]
+fn quantity_stats_test() {
+    let date = Date {
+        year: 2025,
+        month: 7,
+        day: 1,
+    };
+    let amount_record = |date: Date, amount: f64| Record {
+        id: 0,
+        note: String::new(),
+        date,
+        start_time: None,
+        end_time: None,
+        end_date: None,
+        amount: Some(amount),
+        pomodoro: false,
+    };
+    let habit = Habit {
+        name: String::from("Reading"),
+        records: vec![
+            amount_record(date.clone(), 20.0),
+            amount_record(date.clone(), 10.0),
+            amount_record(date.add_days(2), 12.5),
+        ],
+        kind: HabitKind::Quantity {
+            unit: String::from("pages"),
+        },
+        ..Default::default()
+    };
+
+    let stats = habit.get_stats(&clock_on(date.add_days(2)));
+    let quantity = stats.quantity.unwrap();
+    assert_eq!(quantity.unit, "pages");
+    assert_eq!(quantity.total, 42.5);
+    assert_eq!(quantity.average_per_record, Some(42.5 / 3.0));
+    assert_eq!(quantity.average_per_day, Some(42.5 / 2.0));
+    assert_eq!(
+        quantity.per_day,
+        vec![(date.clone(), 30.0), (date.add_days(2), 12.5)]
+    );
+
+    // Records without times take no time, and streaks still work
+    assert_eq!(stats.total_time, Time::from_minutes(0).unwrap());
+    assert_eq!(stats.current_streak, 1);
+}
+
+#[test]
+fn check_in_test() {
+    let date = Date {
+        year: 2025,
+        month: 8,
+        day: 30,
+    };
+    let mut habit = Habit {
+        name: String::from("Floss"),
+        kind: HabitKind::CheckIn,
+        ..Default::default()
+    };
+
+    assert!(habit.check_in(date.clone(), String::new()));
+    assert!(habit.check_in(date.add_days(1), String::from("Late")));
+    assert!(!habit.check_in(date.add_days(1), String::new())); // Already done
+    assert_eq!(habit.records.len(), 2);
+    assert!(habit.is_done_on(&date));
+    assert_eq!(habit.records[1].start_time, None);
+
+    let stats = habit.get_stats(&clock_on(date.add_days(2)));
+    assert_eq!(stats.current_streak, 2);
+    assert!(stats.streak_at_risk);
+}
+
+#[test]
+fn ids_test() {
+    let mut data = UserData {
+        version: UserData::CURRENT_VERSION,
+        timer: None,
+        habits: Vec::new(),
+    };
+    let habit_id = data.add_habit(Habit {
+        name: String::from("Cooking"),
+        ..Default::default()
+    });
+    assert_ne!(habit_id, 0);
+    assert_eq!(data.find_habit_by_name("Cooking"), Some(habit_id));
+
+    let date = Date {
+        year: 2025,
+        month: 9,
+        day: 1,
+    };
+    let habit = data.find_habit_mut(habit_id).unwrap();
+    let first_id = habit.add_record(timed_record(date.clone(), 18, 19));
+    let second_id = habit.add_record(timed_record(date.add_days(1), 18, 19));
+    assert_ne!(first_id, second_id);
+
+    // Edits and removals find records by ID, not position
+    let mut edited = habit.find_record(second_id).unwrap().clone();
+    edited.note = String::from("Lasagna");
+    assert!(habit.update_record(edited));
+    assert_eq!(habit.records[1].note, "Lasagna");
+    assert_eq!(habit.remove_record(first_id).unwrap().date, date);
+    assert!(habit.remove_record(first_id).is_none());
+    assert_eq!(habit.records[0].id, second_id);
+
+    assert!(data.remove_habit(habit_id).is_some());
+    assert!(data.find_habit(habit_id).is_none());
+}
+
+#[test]
+fn read_legacy_file_test() {
+    // Files written before record times were optional
+    fs::write(
+        "test_legacy.ron",
+        "(version:1,habits:[(name:\"Testing\",records:[(note:\"\",\
+         date:(year:2025,month:1,day:1),start_time:(hours:17,minutes:0),\
+         end_time:(hours:17,minutes:20))])])",
+    )
+    .unwrap();
+
+    let data = UserData::try_from_file("test_legacy.ron").unwrap();
+    let record = &data.habits[0].records[0];
+    assert_eq!(
+        record.start_time,
+        Some(Time {
+            hours: 17,
+            minutes: 0,
+        })
+    );
+    assert_eq!(record.end_date, None);
+    assert_eq!(data.habits[0].kind, HabitKind::Timed);
+
+    // IDs are assigned on load
+    assert_ne!(data.habits[0].id, 0);
+    assert_ne!(record.id, 0);
+
+    // Migrated to the current version, with the original kept aside
+    assert_eq!(data.version, UserData::CURRENT_VERSION);
+    let backup = migrations::backup_path("test_legacy.ron", 1);
+    assert!(fs::read_to_string(&backup)
+        .unwrap()
+        .starts_with("(version:1,"));
+}
+
+#[test]
+fn migrations_test() {
+    let file = "(version:1,habits:[(name:\"Testing\",goals:(daily:Some((hours:1,minutes:0))),\
+                records:[(note:\"\",date:(year:2025,month:1,day:1),amount:Some(0.1))])])";
+    let data = migrations::read(file).unwrap();
+    assert_eq!(data.version(), 1);
+    let (data, applied) = migrations::migrate(data);
+    assert_eq!(applied.len(), (UserData::CURRENT_VERSION - 1) as usize);
+    assert_eq!(data.version, UserData::CURRENT_VERSION);
+    assert_ne!(data.habits[0].id, 0);
+    assert_ne!(data.habits[0].records[0].id, 0);
+    // Everything else comes through as it was
+    assert_eq!(
+        data.habits[0].goals.daily,
+        Some(Time::from_minutes(60).unwrap())
+    );
+    assert_eq!(data.habits[0].records[0].amount, Some(0.1));
+
+    // Nothing left to do
+    let current = ron::to_string(&data).unwrap();
+    let (again, applied) = migrations::migrate(migrations::read(&current).unwrap());
+    assert!(applied.is_empty());
+    assert_eq!(again, data);
+
+    // Every version below the current one has a step
+    for version in 1..UserData::CURRENT_VERSION {
+        assert!(migrations::MIGRATIONS
+            .iter()
+            .any(|migration| migration.from_version == version));
+    }
+}
+
+#[test]
+fn newer_version_test() {
+    fs::write("test_newer.ron", "(version:999,habits:[])").unwrap();
+    let error = UserData::read_from_file("test_newer.ron").unwrap_err();
+    assert!(matches!(
+        error,
+        AvalancheError::VersionMismatch { found: 999, .. }
+    ));
+    assert!(error.to_string().contains("newer version"));
+
+    let newer = format!("(version:{},habits:[])", UserData::CURRENT_VERSION + 1);
+    assert!(migrations::read(&newer).is_err());
+    assert!(migrations::read("(version:0,habits:[])").is_err());
+}
+
+#[test]
+fn write_to_file_test() -> Result<(), AvalancheError> {
+    let record = Record {
+        /* This test passes if the write is successful. It doesn't check what
+         * is written. The output in test.ron should be checked manually.
+         */
+        id: 0,
+        note: String::from("Writing test functions"),
+        date: Date {
+            year: 2025,
+            month: 1,
+            day: 1,
+        },
+        start_time: Some(Time {
+            hours: 17,
+            minutes: 0,
+        }),
+        end_time: Some(Time {
+            hours: 17,
+            minutes: 20,
+        }),
+        end_date: None,
+        amount: None,
+        pomodoro: false,
+    };
+    let records = vec![record];
+
+    let habit = Habit {
+        name: String::from("Testing"),
+        records,
+        ..Default::default()
+    };
+    let habits = vec![habit];
+
+    let app_data = UserData {
+        version: UserData::CURRENT_VERSION,
+        timer: None,
+        habits,
+    };
+
+    app_data.write_to_file("test.ron")
+}
+
+#[test]
+fn read_from_file_test() {
+    /* This test writes its own file rather than reading test.ron, so it
+     * doesn't race with write_to_file_test when tests run concurrently.
+     */
+
+    // IDs are set, since missing ones would be assigned on read
+    let record = Record {
+        id: 1,
+        note: String::from("Writing test functions"),
+        date: Date {
+            year: 2025,
+            month: 1,
+            day: 1,
+        },
+        start_time: Some(Time {
+            hours: 17,
+            minutes: 0,
+        }),
+        end_time: Some(Time {
+            hours: 17,
+            minutes: 20,
+        }),
+        end_date: None,
+        amount: None,
+        pomodoro: false,
+    };
+    let records = vec![record];
+
+    let habit = Habit {
+        id: 2,
+        name: String::from("Testing"),
+        records,
+        ..Default::default()
+    };
+    let habits = vec![habit];
+
+    let reference_app_data = UserData {
+        version: UserData::CURRENT_VERSION,
+        timer: None,
+        habits,
+    };
+
+    reference_app_data
+        .write_to_file("test_read.ron")
+        .expect("Failed to write data file.\n");
+    let file_app_data =
+        UserData::read_from_file("test_read.ron").expect("Failed to read data file.\n");
+    assert_eq!(reference_app_data, file_app_data);
+}
+
+#[test]
+fn error_test() {
+    let date = Date {
+        year: 2025,
+        month: 1,
+        day: 1,
+    };
+    let record = timed_record(date.clone(), 18, 17);
+    assert!(matches!(
+        record.length(),
+        Err(AvalancheError::Validation(ValidationError::EndBeforeStart))
+    ));
+    assert!(matches!(
+        record.validate(),
+        Err(AvalancheError::Validation(ValidationError::EndBeforeStart))
+    ));
+    assert!(timed_record(date.clone(), 17, 18).validate().is_ok());
+
+    let mut record = timed_record(date.clone(), 17, 18);
+    record.end_date = Some(Date {
+        year: 2025,
+        month: 2,
+        day: 30,
+    });
+    assert!(matches!(
+        record.validate(),
+        Err(AvalancheError::Validation(ValidationError::InvalidDate(_)))
+    ));
+
+    let mut record = timed_record(date, 17, 18);
+    record.end_time = Some(Time {
+        hours: 24,
+        minutes: 30,
+    });
+    assert!(matches!(
+        record.validate(),
+        Err(AvalancheError::Validation(ValidationError::InvalidTime(_)))
+    ));
+
+    // Parse errors point at the broken spot
+    fs::write("test_broken.ron", "(version:2,\nhabits:[(name:)])").unwrap();
+    match UserData::read_from_file("test_broken.ron") {
+        Err(AvalancheError::Parse { line, column, .. }) => {
+            assert_eq!(line, 2);
+            assert!(column > 1);
+        }
+        other => panic!("Expected a parse error, got {:?}", other),
+    }
+
+    assert!(matches!(
+        UserData::read_from_file("test_missing.ron"),
+        Err(AvalancheError::Io(_))
+    ));
+}
+
+#[test]
+fn backups_test() {
+    // A missing file is a fresh start, a broken one is an error
+    let data = UserData::try_from_file("test_backups_missing.ron").unwrap();
+    assert_eq!(data, UserData::default());
+    fs::write("test_backups.ron", "(version:2,habits:[").unwrap();
+    assert!(UserData::try_from_file("test_backups.ron").is_err());
+
+    fs::write("test_backups.ron.v1.bak", "(version:1,habits:[])").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    fs::write("test_backups.ron.latest.bak", "(version:2,habits:[])").unwrap();
+    let backups = backups::find_backups("test_backups.ron").unwrap();
+    assert_eq!(
+        backups,
+        vec![
+            String::from("./test_backups.ron.latest.bak"),
+            String::from("./test_backups.ron.v1.bak"),
+        ]
+    );
+
+    // The broken file is kept, just under another name
+    let moved_to = backups::move_aside("test_backups.ron").unwrap();
+    assert!(!fs::exists("test_backups.ron").unwrap());
+    assert_eq!(
+        fs::read_to_string(&moved_to).unwrap(),
+        "(version:2,habits:["
+    );
+    fs::remove_file(moved_to).unwrap();
+}
+
+#[test]
+fn rotate_backups_test() {
+    let data = UserData {
+        version: UserData::CURRENT_VERSION,
+        timer: None,
+        habits: vec![Habit {
+            name: String::from("Testing"),
+            records: vec![timed_record(
+                Date {
+                    year: 2025,
+                    month: 1,
+                    day: 1,
+                },
+                17,
+                18,
+            )],
+            ..Default::default()
+        }],
+    };
+    assert_eq!(data.record_count(), 1);
+
+    // Clear out anything left by a run that failed
+    fs::remove_file("test_rotate.ron").unwrap_or(());
+    for path in backups::find_backups("test_rotate.ron").unwrap() {
+        fs::remove_file(path).unwrap();
+    }
+
+    // Nothing to back up before the first save
+    backups::rotate("test_rotate.ron", 2).unwrap();
+    assert!(backups::find_backups("test_rotate.ron").unwrap().is_empty());
+
+    fs::write("test_rotate.ron.v1.bak", "(version:1,habits:[])").unwrap();
+    data.write_to_file("test_rotate.ron").unwrap();
+    assert!(!fs::exists("test_rotate.ron.tmp").unwrap());
+    for _ in 0..4 {
+        backups::rotate("test_rotate.ron", 2).unwrap();
+    }
+
+    // Two timestamped backups are kept, the migration backup is left alone
+    let found = backups::find_backups("test_rotate.ron").unwrap();
+    assert_eq!(found.len(), 3);
+    assert!(found.iter().any(|path| path.ends_with(".v1.bak")));
+
+    let info = backups::backup_info(&found[0]);
+    assert_eq!(info.habit_count, Some(1));
+    assert_eq!(info.record_count, Some(1));
+    let info = backups::backup_info("test_rotate.ron.v1.bak");
+    assert_eq!(info.habit_count, Some(0));
+
+    for path in found {
+        fs::remove_file(path).unwrap();
+    }
+    fs::remove_file("test_rotate.ron").unwrap();
+}
+
+#[test]
+fn edit_test() {
+    let date = Date {
+        year: 2025,
+        month: 1,
+        day: 1,
+    };
+    let mut data = UserData::default();
+    let habit = Habit {
+        id: 1,
+        name: String::from("Testing"),
+        ..Default::default()
+    };
+    let mut record = timed_record(date, 17, 18);
+    record.id = 2;
+
+    let mut undo = Vec::new();
+    let edits = [
+        Edit::InsertHabit { index: 0, habit },
+        Edit::InsertRecord {
+            habit_id: 1,
+            index: 0,
+            record: record.clone(),
+        },
+        Edit::ReplaceRecord {
+            habit_id: 1,
+            record: Record {
+                note: String::from("Edited"),
+                ..record.clone()
+            },
+        },
+    ];
+    for edit in edits {
+        undo.push(edit.apply(&mut data).unwrap());
+    }
+    assert_eq!(data.habits[0].records[0].note, "Edited");
+
+    // Edits to things that don't exist do nothing
+    assert!(Edit::RemoveRecord { habit_id: 1, id: 3 }
+        .apply(&mut data)
+        .is_none());
+    assert!(Edit::RemoveHabit { id: 3 }.apply(&mut data).is_none());
+
+    // Each inverse undoes its edit
+    let edited = data.clone();
+    let inverse = Edit::RemoveHabit { id: 1 }.apply(&mut data).unwrap();
+    assert!(data.habits.is_empty());
+    inverse.apply(&mut data).unwrap();
+    assert_eq!(data, edited);
+
+    while let Some(edit) = undo.pop() {
+        edit.apply(&mut data).unwrap();
+    }
+    assert_eq!(data, UserData::default());
+}
+
+#[test]
+fn journal_test() {
+    let journal = journal::Journal::for_data_file("test_journal.ron");
+    journal.clear().unwrap();
+    assert!(journal.read().unwrap().is_empty());
+
+    let habit = Habit {
+        id: 1,
+        name: String::from("Testing"),
+        ..Default::default()
+    };
+    let edits = vec![
+        Edit::InsertHabit { index: 0, habit },
+        Edit::InsertRecord {
+            habit_id: 1,
+            index: 0,
+            record: timed_record(
+                Date {
+                    year: 2025,
+                    month: 1,
+                    day: 1,
+                },
+                17,
+                18,
+            ),
+        },
+        Edit::RemoveHabit { id: 1 },
+    ];
+    for edit in &edits {
+        journal.append(edit).unwrap();
+    }
+    assert_eq!(journal.read().unwrap(), edits);
+
+    // A line cut off by a crash is ignored
+    let mut contents = fs::read_to_string(&journal.path).unwrap();
+    contents += "RemoveHabit(id:";
+    fs::write(&journal.path, contents).unwrap();
+    assert_eq!(journal.read().unwrap(), edits);
+
+    journal.clear().unwrap();
+    assert!(!fs::exists(&journal.path).unwrap());
+}
+
+#[test]
+fn undo_redo_round_trip_test() {
+    let date: Date = "2025-01-01".parse().unwrap();
+    let record = |id, hours| Record {
+        id,
+        ..timed_record(date.clone(), hours, hours + 1)
+    };
+    let mut data = UserData::default();
+    for id in [1, 2] {
+        data.add_habit(Habit {
+            id,
+            name: format!("Habit {}", id),
+            records: vec![record(id * 10 + 1, 8), record(id * 10 + 2, 9)],
+            ..Default::default()
+        });
+    }
+    let before = data.clone();
+
+    // Later edits move the indexes the earlier ones were made at
+    let mut history = history::History::default();
+    let edits = vec![
+        Edit::RemoveRecord {
+            habit_id: 1,
+            id: 12,
+        },
+        Edit::InsertRecord {
+            habit_id: 1,
+            index: 0,
+            record: record(13, 10),
+        },
+        Edit::RemoveHabit { id: 1 },
+        Edit::ReplaceRecord {
+            habit_id: 2,
+            record: Record {
+                note: String::from("Edited"),
+                ..record(21, 8)
+            },
+        },
+        Edit::Batch(vec![
+            Edit::InsertHabit {
+                index: 0,
+                habit: Habit {
+                    id: 3,
+                    name: String::from("Habit 3"),
+                    ..Default::default()
+                },
+            },
+            Edit::InsertRecord {
+                habit_id: 3,
+                index: 0,
+                record: record(31, 12),
+            },
+        ]),
+    ];
+    for edit in edits {
+        assert!(history.apply(edit, &mut data));
+    }
+    let after = data.clone();
+
+    let undo_all = |history: &mut history::History, data: &mut UserData| {
+        while history.undo(data).is_some() {}
+    };
+    undo_all(&mut history, &mut data);
+    assert_eq!(data, before);
+    while history.redo(&mut data).is_some() {}
+    assert_eq!(data, after);
+    undo_all(&mut history, &mut data);
+    assert_eq!(data, before);
+}
+
+#[test]
+fn history_test() {
+    let mut data = UserData::default();
+    let mut history = history::History::default();
+    assert!(!history.can_undo());
+    assert!(history.undo(&mut data).is_none());
+
+    for id in 1..=3 {
+        let habit = Habit {
+            id,
+            name: format!("Habit {}", id),
+            ..Default::default()
+        };
+        assert!(history.apply(Edit::InsertHabit { index: 0, habit }, &mut data));
+    }
+    assert!(!history.apply(Edit::RemoveHabit { id: 4 }, &mut data));
+    assert!(history.apply(Edit::RemoveHabit { id: 2 }, &mut data));
+    assert_eq!(data.habits.len(), 2);
+
+    // Several levels of undo, then redo
+    assert_eq!(
+        history.undo(&mut data),
+        Some(Edit::InsertHabit {
+            index: 1,
+            habit: Habit {
+                id: 2,
+                name: String::from("Habit 2"),
+                ..Default::default()
+            },
+        })
+    );
+    assert_eq!(data.habits[1].id, 2);
+    history.undo(&mut data).unwrap();
+    history.undo(&mut data).unwrap();
+    assert_eq!(data.habits.len(), 1);
+    assert!(history.can_redo());
+    history.redo(&mut data).unwrap();
+    assert_eq!(data.habits.len(), 2);
+
+    // A new edit drops what could be redone
+    let habit = Habit {
+        id: 5,
+        ..Default::default()
+    };
+    history.apply(Edit::InsertHabit { index: 0, habit }, &mut data);
+    assert!(!history.can_redo());
+    assert!(history.redo(&mut data).is_none());
+
+    history.clear();
+    assert!(!history.can_undo());
+}
+
+#[test]
+fn lock_test() {
+    let lock = lock::DataLock::acquire("test_lock.ron").unwrap();
+    assert!(matches!(
+        lock::DataLock::acquire("test_lock.ron"),
+        Err(AvalancheError::Locked { pid: Some(_) })
+    ));
+    drop(lock);
+    assert!(!fs::exists("test_lock.ron.lock").unwrap());
+
+    // A lock another instance took over isn't removed
+    let lock = lock::DataLock::acquire("test_lock.ron").unwrap();
+    fs::write("test_lock.ron.lock", "1").unwrap();
+    drop(lock);
+    assert!(fs::exists("test_lock.ron.lock").unwrap());
+    fs::remove_file("test_lock.ron.lock").unwrap();
+
+    // Locks left by processes that are gone are taken over where that can
+    // be checked, and count as held elsewhere
+    fs::write("test_lock.ron.lock", u32::MAX.to_string()).unwrap();
+    let result = lock::DataLock::acquire("test_lock.ron");
+    if cfg!(target_os = "linux") {
+        assert!(result.is_ok());
+        drop(result);
+        assert!(!fs::exists("test_lock.ron.lock").unwrap());
+    } else {
+        assert!(matches!(result, Err(AvalancheError::Locked { .. })));
+        fs::remove_file("test_lock.ron.lock").unwrap();
+    }
+}
+
+#[test]
+fn file_state_test() {
+    assert_eq!(lock::FileState::of("test_state_missing.ron").unwrap(), None);
+    let data = UserData::default();
+    data.write_to_file("test_state.ron").unwrap();
+    let state = lock::FileState::of("test_state.ron").unwrap();
+    assert!(state.is_some());
+    assert!(lock::check_unchanged("test_state.ron", &state).is_ok());
+
+    fs::write("test_state.ron", "(version:2,habits:[(name:\"Other\")])").unwrap();
+    assert!(matches!(
+        lock::check_unchanged("test_state.ron", &state),
+        Err(AvalancheError::ChangedOnDisk)
+    ));
+    assert!(matches!(
+        lock::check_unchanged("test_state_missing.ron", &state),
+        Err(AvalancheError::ChangedOnDisk)
+    ));
+}
+
+#[test]
+fn replay_test() {
+    let date = Date {
+        year: 2025,
+        month: 1,
+        day: 1,
+    };
+    let habit = Habit {
+        id: 1,
+        name: String::from("Testing"),
+        ..Default::default()
+    };
+    let mut disk = UserData {
+        version: UserData::CURRENT_VERSION,
+        timer: None,
+        habits: vec![habit.clone()],
+    };
+    disk.habits[0].records.push(Record {
+        id: 2,
+        ..timed_record(date.clone(), 9, 10)
+    });
+
+    // Edits made in this session, to a copy without the record on disk
+    let edits = vec![
+        Edit::ReplaceHabit(Habit {
+            schedule: Schedule::EveryNDays(2),
+            ..habit
+        }),
+        Edit::InsertRecord {
+            habit_id: 1,
+            index: 0,
+            record: Record {
+                id: 3,
+                ..timed_record(date, 17, 18)
+            },
+        },
+        Edit::RemoveHabit { id: 4 },
+    ];
+    assert_eq!(disk.replay(&edits), 1);
+
+    // Both records are kept, with the new schedule
+    let habit = &disk.habits[0];
+    assert_eq!(habit.schedule, Schedule::EveryNDays(2));
+    let mut ids: Vec<u64> = habit.records.iter().map(|record| record.id).collect();
+    ids.sort();
+    assert_eq!(ids, vec![2, 3]);
+
+    // Replaying again, like after a crash between saving and clearing the
+    // journal, doesn't add anything twice
+    let replayed = disk.clone();
+    assert_eq!(disk.replay(&edits), 2);
+    assert_eq!(disk, replayed);
+    let insert_habit = Edit::InsertHabit {
+        index: 1,
+        habit: disk.habits[0].clone(),
+    };
+    assert!(insert_habit.apply(&mut disk).is_none());
+    assert_eq!(disk, replayed);
+}
+
+#[test]
+fn date_time_strings_test() {
+    let date: Date = "2025-01-31".parse().unwrap();
+    assert_eq!(
+        date,
+        Date {
+            year: 2025,
+            month: 1,
+            day: 31,
+        }
+    );
+    assert_eq!(date.to_string(), "2025-01-31");
+    assert!(matches!(
+        "2025-02-30".parse::<Date>(),
+        Err(AvalancheError::Validation(ValidationError::InvalidDate(_)))
+    ));
+    assert!(matches!(
+        "31/01/2025".parse::<Date>(),
+        Err(AvalancheError::Validation(ValidationError::Unreadable(_)))
+    ));
+
+    let time: Time = "7:05".parse().unwrap();
+    assert_eq!(time.to_string(), "07:05");
+    assert!("24:00".parse::<Time>().is_err());
+    assert!("noon".parse::<Time>().is_err());
+}
+
+#[test]
+fn csv_export_test() {
+    let date = Date {
+        year: 2025,
+        month: 1,
+        day: 1,
+    };
+    let mut overnight = timed_record(date.clone(), 23, 1);
+    overnight.end_date = Some(date.add_days(1));
+    overnight.note = String::from("Late, \"really\"");
+    // A 36 hour fast, which the end time alone can't tell from 12 hours
+    let mut fast = timed_record(date.clone(), 20, 8);
+    fast.end_date = Some(date.add_days(2));
+    let data = UserData {
+        version: UserData::CURRENT_VERSION,
+        timer: None,
+        habits: vec![
+            Habit {
+                name: String::from("Reading"),
+                records: vec![timed_record(date.clone(), 17, 18), overnight, fast],
+                ..Default::default()
+            },
+            Habit {
+                name: String::from("Water"),
+                kind: HabitKind::Quantity {
+                    unit: String::from("glasses"),
+                },
+                records: vec![Record {
+                    amount: Some(2.5),
+                    start_time: None,
+                    end_time: None,
+                    ..timed_record(date.clone(), 0, 0)
+                }],
+                ..Default::default()
+            },
+        ],
+    };
+
+    let mut output = Vec::new();
+    data.write_csv(&mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output.clone()).unwrap(),
+        "habit,date,start,end_date,end,duration,note,amount\n\
+         Reading,2025-01-01,17:00,,18:00,60,,\n\
+         Reading,2025-01-01,23:00,2025-01-02,01:00,120,\"Late, \"\"really\"\"\",\n\
+         Reading,2025-01-01,20:00,2025-01-03,08:00,2160,,\n\
+         Water,2025-01-01,,,,,,2.5\n"
+    );
+
+    // Reading it back into empty data gives the same records
+    let mut imported = UserData::default();
+    let report = imported.read_csv(output.as_slice(), None).unwrap();
+    assert_eq!(report.imported, 4);
+    assert_eq!(report.new_habits, vec!["Reading", "Water"]);
+    assert!(report.errors.is_empty());
+    let records = &imported.habits[0].records;
+    assert_eq!(records[1].end_date, data.habits[0].records[1].end_date);
+    assert_eq!(records[1].note, "Late, \"really\"");
+    assert_eq!(records[2].end_date, Some(date.add_days(2)));
+    assert_eq!(
+        records[2].length().unwrap(),
+        Time::from_minutes(36 * 60).unwrap()
+    );
+    assert_eq!(imported.habits[1].records[0].amount, Some(2.5));
+    assert!(matches!(
+        imported.habits[1].kind,
+        HabitKind::Quantity { .. }
+    ));
+}
+
+#[test]
+fn csv_import_test() {
+    let mut data = UserData::default();
+    let id = data.add_habit(Habit {
+        name: String::from("Running"),
+        ..Default::default()
+    });
+
+    // Columns in another order, found by their headers
+    let csv = "Note,Minutes,Start Time,Date,Habit Name\n\
+               Park,45,07:30,2025-03-01,Running\n\
+               ,30,08:00,2025-02-30,Running\n\
+               ,30,25:00,2025-03-02,Running\n\
+               ,,,,Running\n\
+               Late,90,23:00,2025-03-03,Running\n";
+    let report = data.read_csv(csv.as_bytes(), None).unwrap();
+    assert_eq!(report.imported, 2);
+    assert!(report.new_habits.is_empty());
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|error| error.line)
+            .collect::<Vec<_>>(),
+        vec![3, 4, 5]
+    );
+    assert!(report.errors[0].message.contains("Invalid date"));
+    assert!(report.errors[1].message.contains("Invalid time"));
+    assert_eq!(report.errors[2].message, "No date");
+
+    let habit = data.find_habit(id).unwrap();
+    assert_eq!(habit.records[0].end_time, Some("08:15".parse().unwrap()));
+    assert_eq!(habit.records[0].note, "Park");
+    // The duration runs past midnight
+    assert_eq!(habit.records[1].end_time, Some("00:30".parse().unwrap()));
+    assert_eq!(
+        habit.records[1].end_date,
+        Some("2025-03-04".parse().unwrap())
+    );
+
+    // Without an end date column, durations over a day still end on the
+    // right day
+    let csv = "habit,date,start,end,duration\nRunning,2025-03-06,20:00,08:00,2160\n";
+    data.read_csv(csv.as_bytes(), None).unwrap();
+    let record = data.find_habit(id).unwrap().records.last().unwrap();
+    assert_eq!(record.end_date, Some("2025-03-08".parse().unwrap()));
+
+    // Durations too long for a record are errors
+    let csv = "habit,date,start,duration\n\
+               Running,2025-03-07,10:00,4294967295\n\
+               Running,2025-03-07,10:00,40000000\n\
+               Running,2025-03-07,10:00,3932160\n\
+               Running,2025-03-07,10:00,3932159\n";
+    let report = data.read_csv(csv.as_bytes(), None).unwrap();
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.errors.len(), 3);
+    let record = data.find_habit(id).unwrap().records.last().unwrap();
+    assert_eq!(record.length().unwrap(), Time::MAX);
+
+    // Explicit columns, and a header without the required ones
+    let columns = interchange::csv::ColumnMap {
+        habit: 1,
+        date: 0,
+        start: None,
+        end_date: None,
+        end: None,
+        duration: None,
+        note: None,
+        amount: None,
+    };
+    let report = data
+        .read_csv("when,what\n2025-03-05,Running\n".as_bytes(), Some(&columns))
+        .unwrap();
+    assert_eq!(report.imported, 1);
+    let report = data
+        .read_csv("when,what\n2025-03-05,Running\n".as_bytes(), None)
+        .unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(report.errors[0].line, 1);
+
+    // Malformed files are parse errors, not I/O errors
+    assert!(matches!(
+        data.read_csv(&b"habit,da\xffte\n"[..], None),
+        Err(AvalancheError::Parse {
+            line: 1,
+            column: 2,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn json_test() {
+    use interchange::json::Json;
+
+    let mut data = UserData::default();
+    let id = data.add_habit(Habit {
+        name: String::from("Reading"),
+        kind: HabitKind::Quantity {
+            unit: String::from("pages"),
+        },
+        schedule: Schedule::Weekdays(vec![Weekday::Monday, Weekday::Friday]),
+        goals: Goals {
+            daily: Some(Time::from_minutes(30).unwrap()),
+            ..Default::default()
+        },
+        start_date: Some("2025-01-01".parse().unwrap()),
+        ..Default::default()
+    });
+    data.find_habit_mut(id).unwrap().add_record(Record {
+        id: 0,
+        note: String::from("Chapter 3"),
+        date: "2025-01-31".parse().unwrap(),
+        start_time: Some("23:30".parse().unwrap()),
+        end_time: Some("00:15".parse().unwrap()),
+        end_date: Some("2025-02-01".parse().unwrap()),
+        amount: Some(12.5),
+        pomodoro: false,
+    });
+
+    let json = data.to_json().unwrap();
+    assert!(json.contains("\"format_version\": 1"));
+    assert!(json.contains("\"date\": \"2025-01-31\""));
+    assert_eq!(UserData::from_json(&json).unwrap(), data);
+    let habit = &data.habits[0];
+    assert_eq!(Habit::from_json(&habit.to_json().unwrap()).unwrap(), *habit);
+
+    // Only the name and date are needed, and missing IDs are filled in
+    let minimal = r#"{"format_version": 1, "habits": [
+        {"name": "Walk", "records": [{"date": "2025-02-03"}]}]}"#;
+    let imported = UserData::from_json(minimal).unwrap();
+    assert_eq!(imported.habits[0].kind, HabitKind::Timed);
+    assert_ne!(imported.habits[0].records[0].id, 0);
+
+    // Validation errors say where they are
+    let bad_date = r#"{"format_version": 1, "habits": [
+        {"name": "Walk", "records": [{"date": "2025-02-03"}, {"date": "2025-13-01"}]}]}"#;
+    match UserData::from_json(bad_date) {
+        Err(AvalancheError::Invalid { location, error }) => {
+            assert_eq!(location, "habits[0].records[1]");
+            assert!(matches!(error, ValidationError::InvalidDate(_)));
+        }
+        other => panic!("Expected an invalid date, got {:?}", other),
+    }
+    let end_before_start = r#"{"format_version": 1, "habits": [
+        {"name": "Walk", "records": [{"date": "2025-02-03", "start": "10:00", "end": "09:00"}]}]}"#;
+    assert!(matches!(
+        UserData::from_json(end_before_start),
+        Err(AvalancheError::Invalid {
+            error: ValidationError::EndBeforeStart,
+            ..
+        })
+    ));
+    assert!(matches!(
+        Record::from_json(r#"{"date": "2025-02-03", "amount": -1}"#),
+        Err(AvalancheError::Invalid { .. })
+    ));
+    assert!(matches!(
+        UserData::from_json(r#"{"format_version": 2, "habits": []}"#),
+        Err(AvalancheError::VersionMismatch { found: 2, .. })
+    ));
+    assert!(matches!(
+        UserData::from_json("{\"habits\": ["),
+        Err(AvalancheError::Parse { line: 1, .. })
+    ));
+}
+
+#[test]
+fn json_import_test() {
+    use interchange::json::Json;
+
+    let mut data = UserData::default();
+    let id = data.add_habit(Habit {
+        name: String::from("Running"),
+        ..Default::default()
+    });
+    let kept = data.find_habit_mut(id).unwrap().add_record(timed_record(
+        "2025-02-01".parse().unwrap(),
+        9,
+        10,
+    ));
+    let json = r#"{"format_version": 1, "habits": [
+        {"name": "Running", "kind": {"type": "check_in"}, "records": [{"date": "2025-02-03"}]},
+        {"name": "Reading", "records": [{"date": "2025-02-03"}, {"date": "2025-02-04"}]}]}"#;
+    std::fs::write("test_import.json", json).unwrap();
+    let report = data.import_json("test_import.json").unwrap();
+
+    assert_eq!(report.imported, 3);
+    assert_eq!(report.new_habits, vec![String::from("Reading")]);
+    // Running was updated, keeping its ID and records
+    assert_eq!(data.habits.len(), 2);
+    let running = data.find_habit(id).unwrap();
+    assert_eq!(running.kind, HabitKind::CheckIn);
+    assert_eq!(running.records.len(), 2);
+    assert!(running.find_record(kept).is_some());
+
+    // Records with the same ID are updated instead of added again
+    let exported = data.to_json().unwrap().replace("2025-02-03", "2025-02-05");
+    std::fs::write("test_import.json", exported).unwrap();
+    let report = data.import_json("test_import.json").unwrap();
+    assert_eq!((report.imported, report.duplicates), (2, 2));
+    assert_eq!(data.find_habit(id).unwrap().records.len(), 2);
+    assert_eq!(
+        data.habits.iter().map(|h| h.records.len()).sum::<usize>(),
+        4
+    );
+
+    // Renaming a habit to the name of another one is refused
+    let renamed = format!(
+        r#"{{"format_version": 1, "habits": [{{"id": "{}", "name": "Reading"}}]}}"#,
+        id
+    );
+    std::fs::write("test_import.json", renamed).unwrap();
+    let before = data.clone();
+    assert!(matches!(
+        data.import_json("test_import.json"),
+        Err(AvalancheError::Invalid {
+            error: ValidationError::DuplicateName(_),
+            ..
+        })
+    ));
+    assert_eq!(data, before);
+
+    // As are two habits with the same name in the file
+    let twice = r#"{"format_version": 1, "habits": [{"name": "Yoga"}, {"name": "Yoga"}]}"#;
+    std::fs::write("test_import.json", twice).unwrap();
+    match data.import_json("test_import.json") {
+        Err(AvalancheError::Invalid { location, .. }) => assert_eq!(location, "habits[1].name"),
+        other => panic!("Expected a duplicate name, got {:?}", other),
+    }
+    assert_eq!(data, before);
+
+    // A record with the ID of one under another habit is added as a new one
+    let moved = format!(
+        r#"{{"format_version": 1, "habits": [{{"name": "Reading", "records": [
+            {{"id": "{}", "date": "2025-02-06"}}]}}]}}"#,
+        kept
+    );
+    std::fs::write("test_import.json", moved).unwrap();
+    let report = data.import_json("test_import.json").unwrap();
+    assert_eq!(report.imported, 1);
+    let reading = data.find_habit_by_name("Reading").unwrap();
+    let reading = data.find_habit(reading).unwrap();
+    assert_eq!(reading.records.len(), 3);
+    assert!(reading.find_record(kept).is_none());
+    assert!(data.find_habit(id).unwrap().find_record(kept).is_some());
+
+    let empty = r#"{"format_version": 1, "habits": [{"name": " "}]}"#;
+    std::fs::write("test_import.json", empty).unwrap();
+    let before = data.clone();
+    match data.import_json("test_import.json") {
+        Err(AvalancheError::Invalid { location, error }) => {
+            assert_eq!(location, "habits[0].name");
+            assert_eq!(error, ValidationError::EmptyName);
+        }
+        other => panic!("Expected an empty name, got {:?}", other),
+    }
+    assert_eq!(data, before);
+    std::fs::remove_file("test_import.json").unwrap();
+}
+
+// Set AVALANCHE_WRITE_SCHEMA=1 to regenerate the checked in schema
+#[test]
+fn json_schema_test() {
+    let schema = serde_json::to_string_pretty(&interchange::json::schema()).unwrap() + "\n";
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/docs/interchange.schema.json");
+    if std::env::var_os("AVALANCHE_WRITE_SCHEMA").is_some() {
+        std::fs::write(path, &schema).unwrap();
+    }
+    assert_eq!(std::fs::read_to_string(path).unwrap(), schema);
+}
+
+#[test]
+fn ics_export_test() {
+    let mut data = UserData::default();
+    let id = data.add_habit(Habit {
+        name: String::from("Running, outside"),
+        ..Default::default()
+    });
+    let habit = data.find_habit_mut(id).unwrap();
+    let record_id = habit.add_record(Record {
+        id: 0,
+        note: String::from("Long run; felt good\nNew shoes"),
+        date: "2025-03-01".parse().unwrap(),
+        start_time: Some("23:30".parse().unwrap()),
+        end_time: Some("01:00".parse().unwrap()),
+        end_date: Some("2025-03-02".parse().unwrap()),
+        amount: None,
+        pomodoro: false,
+    });
+    habit.check_in("2025-03-03".parse().unwrap(), "x".repeat(100));
+
+    let mut ics = Vec::new();
+    data.write_ics(&mut ics).unwrap();
+    let ics = String::from_utf8(ics).unwrap();
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(ics.contains(&format!("UID:{}@avalanche\r\n", record_id)));
+    assert!(ics.contains("DTSTART:20250301T233000\r\nDTEND:20250302T010000\r\n"));
+    assert!(ics.contains("SUMMARY:Running\\, outside\r\n"));
+    assert!(ics.contains("DESCRIPTION:Long run\\; felt good\\nNew shoes\r\n"));
+    assert!(ics.contains("DTSTART;VALUE=DATE:20250303\r\nDTEND;VALUE=DATE:20250304\r\n"));
+    // Long lines are folded
+    assert!(ics.lines().all(|line| line.len() <= 75));
+    assert!(ics.contains("\r\n xxx"));
+
+    // Importing it again finds the same records
+    let habit = data.find_habit_mut(id).unwrap();
+    let report = habit
+        .read_ics(ics.as_bytes(), |date, time| (date, time))
+        .unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(report.duplicates, 2);
+
+    let mut copy = Habit::default();
+    let report = copy
+        .read_ics(ics.as_bytes(), |date, time| (date, time))
+        .unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(copy.records[0].note, "Long run; felt good\nNew shoes");
+    assert_eq!(
+        copy.records[0].end_date,
+        Some("2025-03-02".parse().unwrap())
+    );
+    assert_eq!(copy.records[1].note, "x".repeat(100));
+    assert_eq!(copy.records[1].start_time, None);
+}
+
+#[test]
+fn ics_import_test() {
+    // Two hours ahead of UTC
+    fn to_local(date: Date, time: Time) -> (Date, Time) {
+        let minutes = time.as_minutes() + 120;
+        (
+            date.add_days((minutes / 1440) as i64),
+            Time::of_day(minutes),
+        )
+    }
+    let ics = "BEGIN:VCALENDAR\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:abc@example.com\r\n\
+        DTSTART:20250301T070000\r\n\
+        DTEND:20250301T074500\r\n\
+        SUMMARY:Morning \r\n run\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART:20250301T230000Z\r\n\
+        DURATION:PT1H30M\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART;TZID=\"Europe/Paris\":20250302T100000\r\n\
+        DTEND;TZID=\"Europe/Paris\":20250302T090000\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        SUMMARY:No start\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART:20250301T070000\r\n\
+        DTEND:20250301T074500\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    let mut habit = Habit {
+        name: String::from("Running"),
+        ..Default::default()
+    };
+    let report = habit.read_ics(ics.as_bytes(), to_local).unwrap();
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.duplicates, 1);
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(report.errors[0].line, 13);
+    assert_eq!(report.errors[1].message, "No DTSTART");
+
+    // Floating times are kept and folded lines are joined
+    assert_eq!(habit.records[0].start_time, Some("07:00".parse().unwrap()));
+    assert_eq!(habit.records[0].note, "Morning run");
+    // UTC times are converted, and the duration runs past midnight
+    let record = &habit.records[1];
+    assert_eq!(record.date, "2025-03-02".parse().unwrap());
+    assert_eq!(record.start_time, Some("01:00".parse().unwrap()));
+    assert_eq!(record.end_time, Some("02:30".parse().unwrap()));
+    assert_eq!(record.end_date, None);
+
+    // Bad seconds and durations too long to count are errors, not panics
+    let ics = "BEGIN:VCALENDAR\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART:20250101T10000é\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART:20250101T100000\r\n\
+        DURATION:P999999W\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        DTSTART:20250101T100000\r\n\
+        DURATION:PT4294967295M\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+    let report = habit.read_ics(ics.as_bytes(), to_local).unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(report.errors.len(), 3);
+
+    // Events are time spent, so other kinds of habits can't take them
+    for kind in [
+        HabitKind::Quit,
+        HabitKind::CheckIn,
+        HabitKind::Quantity {
+            unit: String::from("pages"),
+        },
+    ] {
+        let mut habit = Habit {
+            name: String::from("Reading"),
+            kind,
+            ..Default::default()
+        };
+        assert!(matches!(
+            habit.read_ics(ics.as_bytes(), to_local),
+            Err(AvalancheError::Invalid {
+                error: ValidationError::WrongKind,
+                ..
+            })
+        ));
+        assert!(habit.records.is_empty());
+    }
+}
+
+#[test]
+fn tracker_import_test() {
+    use interchange::trackers::{DateHabitValue, LoopHabitTracker};
+    use interchange::Source;
+
+    let checkmarks = "Date,Meditate,Water,\n\
+        2025-03-03,2,1.500,\n\
+        2025-03-02,0,0.000,\n\
+        2025-03-01,2,oops,\n\
+        2025-02-30,2,2.000,\n";
+    let imported = LoopHabitTracker.read(&mut checkmarks.as_bytes()).unwrap();
+    assert_eq!(imported.records.len(), 3);
+    assert_eq!(imported.records[1].1, "Water");
+    assert_eq!(imported.records[1].2.amount, Some(1.5));
+    assert_eq!(imported.errors.len(), 2);
+    assert_eq!(imported.errors[0].line, 4);
+    assert_eq!(imported.errors[1].line, 5);
+
+    let mut data = UserData::default();
+    let meditate = data.add_habit(Habit {
+        name: String::from("Meditate"),
+        kind: HabitKind::CheckIn,
+        ..Default::default()
+    });
+    data.find_habit_mut(meditate)
+        .unwrap()
+        .check_in("2025-03-03".parse().unwrap(), String::new());
+    let report = data.merge(imported);
+    assert_eq!(report.imported, 2);
+    assert_eq!(report.duplicates, 1);
+    assert_eq!(report.new_habits, vec![String::from("Water")]);
+    let water = data.find_habit_by_name("Water").unwrap();
+    assert_eq!(
+        data.find_habit(water).unwrap().kind,
+        HabitKind::Quantity {
+            unit: String::from(interchange::PLACEHOLDER_UNIT)
+        }
+    );
+
+    // The header is optional
+    let log = "date,habit,value\n\
+        2025-03-04,Meditate,yes\n\
+        2025-03-04,Water,2\n\
+        2025-03-05,Water,0\n\
+        2025-03-05,Water,lots\n\
+        2025-03-06,Stretch,\n";
+    let imported = DateHabitValue.read(&mut log.as_bytes()).unwrap();
+    assert_eq!(imported.records.len(), 3);
+    assert_eq!(imported.errors.len(), 1);
+    assert_eq!(imported.errors[0].line, 5);
+    let imported = DateHabitValue
+        .read(&mut "2025-03-04,Meditate,\n".as_bytes())
+        .unwrap();
+    assert_eq!(imported.records.len(), 1);
+
+    // A dry run doesn't change anything, and importing twice skips
+    // everything the second time
+    std::fs::write("test_log.csv", log).unwrap();
+    let before = data.clone();
+    let report = data.dry_run(&DateHabitValue, "test_log.csv").unwrap();
+    assert_eq!(report.imported, 3);
+    assert_eq!(report.new_habits, vec![String::from("Stretch")]);
+    assert_eq!(data, before);
+    data.import_from(&DateHabitValue, "test_log.csv").unwrap();
+    let report = data.import_from(&DateHabitValue, "test_log.csv").unwrap();
+    std::fs::remove_file("test_log.csv").unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(report.duplicates, 3);
+
+    // Records that don't fit an existing habit's kind are errors
+    data.add_habit(Habit {
+        name: String::from("Running"),
+        ..Default::default()
+    });
+    let log = "2025-03-07,Running,5\n2025-03-07,Meditate,2\n2025-03-07,Water,yes\n";
+    let imported = DateHabitValue.read(&mut log.as_bytes()).unwrap();
+    let report = data.merge(imported);
+    assert_eq!(report.imported, 0);
+    assert_eq!(
+        report
+            .errors
+            .iter()
+            .map(|error| error.line)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert!(report.errors[0].message.starts_with("Running:"));
+}
+
+#[test]
+fn timer_test() -> Result<(), AvalancheError> {
+    let start = FixedClock {
+        date: "2025-03-01".parse()?,
+        time: "23:30".parse()?,
+    };
+    let mut data = UserData::default();
+    let id = data.add_habit(Habit {
+        name: String::from("Running"),
+        ..Default::default()
+    });
+    assert_eq!(data.stop_timer(&start), None);
+
+    Edit::SetTimer(Some(Timer::start(id, &start))).apply(&mut data);
+    // The timer is kept in the data file
+    data.write_to_file("test_timer.ron")?;
+    let mut data = UserData::try_from_file("test_timer.ron")?;
+    std::fs::remove_file("test_timer.ron")?;
+
+    let end = FixedClock {
+        date: "2025-03-02".parse()?,
+        time: "00:15".parse()?,
+    };
+    let timer = data.timer.clone().unwrap();
+    assert_eq!(timer.elapsed_seconds(&end), 45 * 60);
+    assert_eq!(timer::format_seconds(45 * 60 + 9), "45:09");
+    assert_eq!(timer::format_seconds(3600 + 5 * 60 + 9), "1:05:09");
+
+    // Stopping adds the record, and undoing it starts the timer again
+    let (edit, record) = data.stop_timer(&end).unwrap();
+    let record = record.unwrap();
+    assert_eq!(record.length()?, Time::from_minutes(45).unwrap());
+    assert_eq!(record.end_date, Some("2025-03-02".parse()?));
+    let undo = edit.apply(&mut data).unwrap();
+    assert_eq!(data.timer, None);
+    assert_eq!(data.find_habit(id).unwrap().records, vec![record]);
+    undo.apply(&mut data);
+    assert_eq!(data.timer, Some(timer));
+    assert!(data.find_habit(id).unwrap().records.is_empty());
+
+    // A batch that can't be applied changes nothing
+    let before = data.clone();
+    let batch = Edit::Batch(vec![Edit::SetTimer(None), Edit::RemoveHabit { id: id + 1 }]);
+    assert_eq!(batch.apply(&mut data), None);
+    assert_eq!(data, before);
+
+    // Timers for removed habits are only stopped
+    data.remove_habit(id);
+    assert_eq!(data.stop_timer(&end), Some((Edit::SetTimer(None), None)));
+    Ok(())
+}
+
+#[test]
+fn pomodoro_test() -> Result<(), AvalancheError> {
+    let at = |date: &str, time: &str| -> Result<FixedClock, AvalancheError> {
+        Ok(FixedClock {
+            date: date.parse()?,
+            time: time.parse()?,
+        })
+    };
+    let mut habit = Habit {
+        id: 1,
+        name: String::from("Writing"),
+        ..Default::default()
+    };
+    let mut session = pomodoro::Pomodoro::start(habit.id, 25, 5, &at("2025-03-01", "23:50")?);
+    assert_eq!(
+        session.remaining_seconds(&at("2025-03-02", "00:05")?),
+        10 * 60
+    );
+    assert_eq!(session.advance(&at("2025-03-02", "00:14")?), None);
+
+    // A finished work interval is a record, even past midnight
+    let record = session
+        .advance(&at("2025-03-02", "00:15")?)
+        .unwrap()
+        .unwrap();
+    assert!(record.pomodoro);
+    assert_eq!(record.start_time, Some("23:50".parse()?));
+    assert_eq!(record.end_date, Some("2025-03-02".parse()?));
+    assert_eq!(record.length()?, Time::from_minutes(25).unwrap());
+    assert_eq!(
+        (session.phase, session.completed),
+        (pomodoro::Phase::Break, 1)
+    );
+    habit.records.push(record);
+
+    // Breaks log nothing, and start from when they're noticed
+    assert_eq!(session.advance(&at("2025-03-02", "00:30")?), Some(None));
+    assert_eq!(session.phase, pomodoro::Phase::Work);
+    assert_eq!(
+        session.remaining_seconds(&at("2025-03-02", "00:30")?),
+        25 * 60
+    );
+
+    habit.records.push(Record {
+        id: 2,
+        note: String::new(),
+        date: "2025-03-02".parse()?,
+        start_time: Some("09:00".parse()?),
+        end_time: Some("10:00".parse()?),
+        end_date: None,
+        amount: None,
+        pomodoro: false,
+    });
+    let stats = habit.get_stats(&at("2025-03-02", "12:00")?);
+    assert_eq!(stats.pomodoros, 1);
+    assert_eq!(stats.total_time, Time::from_minutes(85).unwrap());
+    Ok(())
+}
+
+#[test]
+fn config_test() -> Result<(), AvalancheError> {
+    use crate::config::Config;
+
+    // Anything left out keeps its default
+    std::fs::write(
+        "test_config.ron",
+        r#"(
+            date_format: "%d/%m/%Y",
+            time_format: "%-I:%M %p",
+            week_start: Sunday,
+            default_habit_kind: Quantity(unit: "pages"),
+            pomodoro: (work_minutes: 50),
+        )"#,
+    )?;
+    let config = Config::try_from_file("test_config.ron")?;
+    std::fs::remove_file("test_config.ron")?;
+    assert_eq!(config.backup_count, backups::DEFAULT_BACKUP_COUNT);
+    assert_eq!(config.pomodoro.work_minutes, 50);
+    assert_eq!(config.pomodoro.break_minutes, 5);
+    assert_eq!(
+        config.default_habit_kind,
+        HabitKind::Quantity {
+            unit: String::from("pages")
+        }
+    );
+    assert_eq!(config.format_date(&"2025-03-09".parse()?), "09/03/2025");
+    assert_eq!(config.format_time(&"14:05".parse()?), "2:05 PM");
+    assert_eq!(
+        Config::default().format_date(&"2025-03-09".parse()?),
+        "2025-3-9"
+    );
+    assert_eq!(
+        Config::try_from_file("test_missing_config.ron")?,
+        Config::default()
+    );
+
+    // Formats that can't be used are caught when the config is read
+    for format in ["%Q", "%H:%M"] {
+        let config = Config {
+            date_format: String::from(format),
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(AvalancheError::Invalid { location, .. }) if location == "date_format"
+        ));
+    }
+    std::fs::write("test_config.ron", "(colour: true)")?;
+    let result = Config::try_from_file("test_config.ron");
+    std::fs::remove_file("test_config.ron")?;
+    assert!(matches!(result, Err(AvalancheError::Parse { .. })));
+
+    // Weeks starting on Sunday move the weekly goal's periods
+    let sunday: Date = "2025-03-09".parse()?;
+    assert_eq!(Period::Week.start_with(&sunday, Weekday::Sunday), sunday);
+    assert_eq!(
+        Period::Week.start_with(&"2025-03-15".parse()?, Weekday::Sunday),
+        sunday
+    );
+    assert_eq!(Period::Week.start_of(&sunday), "2025-03-03".parse()?);
+    let habit = Habit {
+        name: String::from("Reading"),
+        goals: Goals {
+            weekly: Some(Time::from_minutes(60).unwrap()),
+            ..Default::default()
+        },
+        records: vec![Record {
+            id: 1,
+            note: String::new(),
+            date: sunday.clone(),
+            start_time: Some("10:00".parse()?),
+            end_time: Some("11:00".parse()?),
+            end_date: None,
+            amount: None,
+            pomodoro: false,
+        }],
+        ..Default::default()
+    };
+    let clock = clock_on("2025-03-10".parse()?);
+    let iso = habit.get_stats(&clock).weekly_goal.unwrap();
+    assert_eq!((iso.current.as_minutes(), iso.met), (0, 1));
+    let from_sunday = habit
+        .get_stats_with(&clock, Weekday::Sunday)
+        .weekly_goal
+        .unwrap();
+    assert_eq!((from_sunday.current.as_minutes(), from_sunday.met), (60, 0));
+    Ok(())
+}
+
+#[test]
+fn config_overrides_test() -> Result<(), AvalancheError> {
+    use crate::config::{self, Config, Overrides};
+
+    let mut config: Config = ron::from_str(
+        r#"(date_format: "%d.%m.%Y", time_format: "%H.%M", week_start: Sunday, backup_count: 3)"#,
+    )?;
+    // Only what's given is replaced
+    let overrides = Overrides {
+        date_format: Some(String::from("%Y/%m/%d")),
+        week_start: Some(Weekday::Saturday),
+        default_habit_kind: Some(config::parse_habit_kind("quantity:pages").unwrap()),
+        ..Default::default()
+    };
+    config.apply(&overrides)?;
+    assert_eq!(config.date_format, "%Y/%m/%d");
+    assert_eq!(config.week_start, Weekday::Saturday);
+    assert_eq!(config.time_format, "%H.%M");
+    assert_eq!(config.backup_count, 3);
+    assert_eq!(
+        config.default_habit_kind,
+        HabitKind::Quantity {
+            unit: String::from("pages")
+        }
+    );
+
+    // Values that can't be used are refused like in the file
+    assert_eq!(config::parse_weekday(" SUNDAY"), Ok(Weekday::Sunday));
+    assert!(config::parse_weekday("someday").is_err());
+    assert_eq!(config::parse_habit_kind("check-in"), Ok(HabitKind::CheckIn));
+    assert!(config::parse_habit_kind("timed:x").is_err());
+    let overrides = Overrides {
+        time_format: Some(String::from("%Y")),
+        ..Default::default()
+    };
+    assert!(Config::default().apply(&overrides).is_err());
+    Ok(())
+}
+
+#[test]
+fn profiles_test() -> Result<(), AvalancheError> {
+    use crate::config::Config;
+
+    let config: Config =
+        ron::from_str(r#"(profiles: { "work": "/tmp/work.ron", "home": "~/home.ron" })"#)?;
+    assert_eq!(
+        config.profile_file_path("work"),
+        Some(String::from("/tmp/work.ron"))
+    );
+    let home = config.profile_file_path("home").unwrap();
+    assert!(home.ends_with("home.ron") && !home.starts_with('~'));
+    assert_eq!(config.profile_file_path("gym"), None);
+    Ok(())
 }
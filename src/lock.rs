@@ -0,0 +1,120 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, OpenOptions};
+use std::hash::Hasher;
+use std::io::{ErrorKind, Write};
+use std::process;
+use std::time::SystemTime;
+
+use crate::AvalancheError;
+
+/* Keeps two instances from saving over each other. The lock is advisory: a
+ * file next to the data file holding the process ID of the instance that
+ * has it open. It's removed when the DataLock is dropped, unless another
+ * instance has taken it over since.
+ */
+pub struct DataLock {
+    path: String,
+}
+
+impl DataLock {
+    pub fn acquire(filename: &str) -> Result<DataLock, AvalancheError> {
+        let path = format!("{}.lock", filename);
+        if let Some(lock) = Self::create(&path)? {
+            return Ok(lock);
+        }
+        let pid = read_pid(&path);
+        // Locks left behind by a crash don't count
+        if pid.is_none_or(is_running) {
+            return Err(AvalancheError::Locked { pid });
+        }
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+            _ => (),
+        }
+        // Another instance may have replaced the stale lock first
+        Self::create(&path)?.ok_or_else(|| AvalancheError::Locked {
+            pid: read_pid(&path),
+        })
+    }
+
+    // Takes the lock even if another instance holds it
+    pub fn take_over(filename: &str) -> Result<DataLock, AvalancheError> {
+        let path = format!("{}.lock", filename);
+        let mut file = fs::File::create(&path)?;
+        file.write_all(process::id().to_string().as_bytes())?;
+        Ok(DataLock { path })
+    }
+
+    // None if the lock file already exists
+    fn create(path: &str) -> Result<Option<DataLock>, AvalancheError> {
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let lock = DataLock {
+            path: path.to_string(),
+        };
+        file.write_all(process::id().to_string().as_bytes())?;
+        Ok(Some(lock))
+    }
+}
+
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        // Leave the lock alone if another instance opened the file anyway
+        if read_pid(&self.path) == Some(process::id()) {
+            fs::remove_file(&self.path).unwrap_or(());
+        }
+    }
+}
+
+fn read_pid(path: &str) -> Option<u32> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| contents.trim().parse().ok())
+}
+
+#[cfg(target_os = "linux")]
+fn is_running(pid: u32) -> bool {
+    std::path::Path::new("/proc").join(pid.to_string()).exists()
+}
+
+// Without /proc there's no way to check, so the lock counts as held
+#[cfg(not(target_os = "linux"))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+/* What the data file looked like when it was last read or written. Saving
+ * compares it against the file on disk to catch changes made by anything
+ * else.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct FileState {
+    pub modified: Option<SystemTime>,
+    pub hash: u64,
+}
+
+impl FileState {
+    // None if the file doesn't exist
+    pub fn of(filename: &str) -> Result<Option<FileState>, AvalancheError> {
+        if !fs::exists(filename)? {
+            return Ok(None);
+        }
+        let contents = fs::read(filename)?;
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&contents);
+        Ok(Some(FileState {
+            modified: fs::metadata(filename)?.modified().ok(),
+            hash: hasher.finish(),
+        }))
+    }
+}
+
+pub fn check_unchanged(filename: &str, expected: &Option<FileState>) -> Result<(), AvalancheError> {
+    if FileState::of(filename)? != *expected {
+        return Err(AvalancheError::ChangedOnDisk);
+    }
+    Ok(())
+}
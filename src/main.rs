@@ -1,19 +1,132 @@
-use dirs::data_dir;
-use AVALANCHE::{Date, Habit, Record, Time, UserData};
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
+use AVALANCHE::config::Config;
+use AVALANCHE::history::History;
+use AVALANCHE::interchange;
+use AVALANCHE::journal::Journal;
+use AVALANCHE::lock::DataLock;
+use AVALANCHE::{
+    backups, new_id, AvalancheError, Clock, Date, Edit, GoalProgress, Goals, Habit, HabitKind,
+    Record, Schedule, SystemClock, Time, UserData, ValidationError, Weekday,
+};
 
 mod app;
+mod cli;
+#[cfg(test)]
+mod cli_tests;
 
 fn main() {
-    let filename = format!("{}/{}", data_dir().unwrap().to_str().unwrap(), ".avalanche");
+    let cli = cli::Cli::parse_args();
+    let config = read_config(&cli);
+
+    // At most one of these is left after parsing
+    let profile = cli.profile.clone();
+    let filename = match (&cli.file, &profile) {
+        (Some(file), _) => Some(file.clone()),
+        (None, Some(profile)) => match config.profile_file_path(profile) {
+            Some(filename) => Some(filename),
+            None => {
+                eprintln!(
+                    "avalanche: No profile called {} in the config file",
+                    profile
+                );
+                process::exit(cli::EXIT_USAGE);
+            }
+        },
+        (None, None) => config.data_file_path(),
+    };
+    let filename = match filename {
+        Some(filename) => {
+            if let Some(dir) = Path::new(&filename).parent() {
+                fs::create_dir_all(dir).unwrap_or(());
+            }
+            filename
+        }
+        None => {
+            eprintln!("Could not find a data directory to keep the data file in");
+            process::exit(cli::EXIT_ERROR);
+        }
+    };
+
+    // Without a command, the TUI opens
+    if cli.command.is_some() {
+        process::exit(cli::run(cli, &filename, &config));
+    }
 
     let app_data = app::AppData {
         data_file_name: filename.clone(),
-        user_data: UserData::try_from_file(&filename),
+        user_data: UserData::default(),
         selected_habit: None,
         unsaved_changes: false,
+        journal: Journal::for_data_file(&filename),
+        history: History::default(),
+        lock: None,
+        read_only: false,
+        file_state: None,
+        pomodoro: None,
+        config: config.clone(),
+        profile,
     };
 
     let mut siv = cursive::default();
     siv.set_user_data(app_data);
-    app::habits_page::draw(siv);
+    match DataLock::acquire(&filename) {
+        Ok(lock) => {
+            siv.user_data::<app::AppData>().unwrap().lock = Some(lock);
+            app::open_data_file(&mut siv);
+        }
+        Err(error) => app::lock_warning(&mut siv, &error),
+    }
+
+    // Autosave is off unless an interval in seconds is given
+    let autosave_secs = config.autosave_secs;
+    if autosave_secs > 0 {
+        let sink = siv.cb_sink().clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(autosave_secs));
+            if sink.send(Box::new(app::autosave)).is_err() {
+                break;
+            }
+        });
+    }
+
+    // Keeps the running timer and pomodoro ticking
+    let sink = siv.cb_sink().clone();
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_secs(1));
+        if sink.send(Box::new(app::tick)).is_err() {
+            break;
+        }
+    });
+
+    siv.run();
+}
+
+/* The config file, with what's given on the command line or in the
+ * environment on top. Exits if the file can't be read, rather than carrying
+ * on with settings the user didn't ask for.
+ */
+fn read_config(cli: &cli::Cli) -> Config {
+    let path = match &cli.config {
+        Some(path) => Some(path.clone()),
+        None => Config::default_path().map(|path| path.to_string_lossy().to_string()),
+    };
+    let mut config = match &path {
+        Some(path) => Config::try_from_file(path).unwrap_or_else(|error| {
+            eprintln!(
+                "avalanche: Could not read the config file {}: {}",
+                path, error
+            );
+            process::exit(cli::EXIT_ERROR);
+        }),
+        None => Config::default(),
+    };
+    if let Err(error) = config.apply(&cli.overrides.clone().into()) {
+        eprintln!("avalanche: {}", error);
+        process::exit(cli::EXIT_USAGE);
+    }
+    config
 }
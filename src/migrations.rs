@@ -0,0 +1,180 @@
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::{AvalancheError, UserData};
+
+/* Upgrades data from older versions of the file format. A file is read
+ * into the types of the version it was written as, then each step turns
+ * one version into the next, until it's the current UserData.
+ *
+ * The version only goes up when old files can't be read as they are. Fields
+ * added with serde defaults, like schedules, habit kinds or the end dates
+ * and amounts of records, don't need a step: files written before them
+ * still load, with the defaults filled in. That's why version 1 covers
+ * everything before stable IDs.
+ */
+pub struct Migration {
+    // The step upgrades data from this version to the next one
+    pub from_version: u16,
+    pub description: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    from_version: 1,
+    description: "Assign IDs to habits and records",
+}];
+
+// A data file in the shape of the version it was written as
+pub enum VersionedData {
+    V1(v1::UserData),
+    Current(UserData),
+}
+
+impl VersionedData {
+    pub fn version(&self) -> u16 {
+        match self {
+            VersionedData::V1(_) => 1,
+            VersionedData::Current(_) => UserData::CURRENT_VERSION,
+        }
+    }
+
+    // Moves up one version
+    fn upgrade(self) -> VersionedData {
+        match self {
+            VersionedData::V1(data) => VersionedData::Current(data.upgrade()),
+            current => current,
+        }
+    }
+}
+
+// Only the version is read, the rest of the file is ignored
+#[derive(Deserialize)]
+struct VersionHeader {
+    version: u16,
+}
+
+pub fn file_version(contents: &str) -> Result<u16, AvalancheError> {
+    let header: VersionHeader = ron::from_str(contents)?;
+    Ok(header.version)
+}
+
+// Reads a data file as the version it says it is
+pub fn read(contents: &str) -> Result<VersionedData, AvalancheError> {
+    let version = file_version(contents)?;
+    match version {
+        1 => Ok(VersionedData::V1(from_str(contents)?)),
+        UserData::CURRENT_VERSION => Ok(VersionedData::Current(from_str(contents)?)),
+        _ => Err(AvalancheError::VersionMismatch {
+            found: version,
+            supported: UserData::CURRENT_VERSION,
+        }),
+    }
+}
+
+fn from_str<T: DeserializeOwned>(contents: &str) -> Result<T, AvalancheError> {
+    // Record times used to be required, so older files store them without
+    // Some(...)
+    Ok(ron::Options::default()
+        .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+        .from_str(contents)?)
+}
+
+/* Applies every step up to the current version. Returns the data with the
+ * descriptions of the steps that were applied.
+ */
+pub fn migrate(mut data: VersionedData) -> (UserData, Vec<&'static str>) {
+    let mut applied = Vec::new();
+    loop {
+        match data {
+            VersionedData::Current(data) => return (data, applied),
+            older => {
+                let version = older.version();
+                applied.extend(
+                    MIGRATIONS
+                        .iter()
+                        .filter(|migration| migration.from_version == version)
+                        .map(|migration| migration.description),
+                );
+                data = older.upgrade();
+            }
+        }
+    }
+}
+
+// Where a file is copied before it is migrated away from version
+pub fn backup_path(filename: &str, version: u16) -> String {
+    format!("{}.v{}.bak", filename, version)
+}
+
+// Files from before habits and records had IDs
+pub mod v1 {
+    use serde::Deserialize;
+
+    use crate::{new_id, Date, Goals, HabitKind, Schedule, Time};
+
+    #[derive(Deserialize)]
+    pub struct UserData {
+        pub habits: Vec<Habit>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Habit {
+        pub name: String,
+        pub records: Vec<Record>,
+        #[serde(default)]
+        pub goals: Goals,
+        #[serde(default)]
+        pub schedule: Schedule,
+        #[serde(default)]
+        pub kind: HabitKind,
+        #[serde(default)]
+        pub start_date: Option<Date>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Record {
+        pub note: String,
+        pub date: Date,
+        #[serde(default)]
+        pub start_time: Option<Time>,
+        #[serde(default)]
+        pub end_time: Option<Time>,
+        #[serde(default)]
+        pub end_date: Option<Date>,
+        #[serde(default)]
+        pub amount: Option<f64>,
+    }
+
+    impl UserData {
+        // Version 2 gives every habit and record an ID
+        pub fn upgrade(self) -> crate::UserData {
+            let habits = self.habits.into_iter().map(|habit| crate::Habit {
+                id: new_id(),
+                name: habit.name,
+                records: habit
+                    .records
+                    .into_iter()
+                    .map(|record| crate::Record {
+                        id: new_id(),
+                        note: record.note,
+                        date: record.date,
+                        start_time: record.start_time,
+                        end_time: record.end_time,
+                        end_date: record.end_date,
+                        amount: record.amount,
+                        pomodoro: false,
+                    })
+                    .collect(),
+                goals: habit.goals,
+                schedule: habit.schedule,
+                kind: habit.kind,
+                start_date: habit.start_date,
+            });
+            crate::UserData {
+                version: crate::UserData::CURRENT_VERSION,
+                habits: habits.collect(),
+                timer: None,
+            }
+        }
+    }
+}
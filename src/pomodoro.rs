@@ -0,0 +1,106 @@
+use crate::{new_id, Clock, Date, Record, Time};
+
+pub const DEFAULT_WORK_MINUTES: u32 = 25;
+pub const DEFAULT_BREAK_MINUTES: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    Work,
+    Break,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Work => "work",
+            Phase::Break => "break",
+        }
+    }
+}
+
+/* A focus session: work and break intervals taking turns until it's
+ * stopped. Each work interval that runs to the end becomes a record. Unlike
+ * the timer, sessions only last while the app is open.
+ */
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pomodoro {
+    pub habit_id: u64,
+    pub work_minutes: u32,
+    pub break_minutes: u32,
+    pub phase: Phase,
+    // Work intervals finished so far
+    pub completed: u32,
+    phase_date: Date,
+    phase_start: Time,
+    // Clock::timestamp when the phase started
+    phase_started_at: i64,
+}
+
+impl Pomodoro {
+    pub fn start(
+        habit_id: u64,
+        work_minutes: u32,
+        break_minutes: u32,
+        clock: &dyn Clock,
+    ) -> Pomodoro {
+        Pomodoro {
+            habit_id,
+            work_minutes,
+            break_minutes,
+            phase: Phase::Work,
+            completed: 0,
+            phase_date: clock.today(),
+            phase_start: clock.now(),
+            phase_started_at: clock.timestamp(),
+        }
+    }
+
+    pub fn phase_seconds(&self) -> i64 {
+        let minutes = match self.phase {
+            Phase::Work => self.work_minutes,
+            Phase::Break => self.break_minutes,
+        };
+        minutes as i64 * 60
+    }
+
+    pub fn remaining_seconds(&self, clock: &dyn Clock) -> i64 {
+        (self.phase_seconds() - (clock.timestamp() - self.phase_started_at)).max(0)
+    }
+
+    /* Moves on to the next phase if this one is over, returning the record
+     * for a finished work interval. The next phase starts now rather than
+     * when the last one ended, so time the app spent suspended isn't
+     * counted as more intervals.
+     */
+    pub fn advance(&mut self, clock: &dyn Clock) -> Option<Option<Record>> {
+        if self.remaining_seconds(clock) > 0 {
+            return None;
+        }
+        let record = match self.phase {
+            Phase::Work => {
+                self.completed += 1;
+                let end = self.phase_start.as_minutes() + self.work_minutes;
+                Some(Record {
+                    id: new_id(),
+                    note: String::new(),
+                    date: self.phase_date.clone(),
+                    start_time: Some(self.phase_start.clone()),
+                    end_time: Some(Time::of_day(end)),
+                    end_date: Some(self.phase_date.add_days((end / 1440) as i64))
+                        .filter(|date| *date != self.phase_date),
+                    amount: None,
+                    pomodoro: true,
+                })
+            }
+            Phase::Break => None,
+        };
+        self.phase = match self.phase {
+            Phase::Work => Phase::Break,
+            Phase::Break => Phase::Work,
+        };
+        self.phase_date = clock.today();
+        self.phase_start = clock.now();
+        self.phase_started_at = clock.timestamp();
+        Some(record)
+    }
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{new_id, Clock, Date, Edit, Record, Time, UserData};
+
+/* A session that's being timed. It's kept in the data file so it keeps
+ * running while the app is closed, and stopping it adds a record with the
+ * start and end times. Only one timer runs at a time.
+ */
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Timer {
+    pub habit_id: u64,
+    pub date: Date,
+    pub start: Time,
+    // Clock::timestamp when it started, for counting the seconds
+    pub started_at: i64,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Timer {
+    pub fn start(habit_id: u64, clock: &dyn Clock) -> Timer {
+        Timer {
+            habit_id,
+            date: clock.today(),
+            start: clock.now(),
+            started_at: clock.timestamp(),
+            note: String::new(),
+        }
+    }
+
+    pub fn elapsed_seconds(&self, clock: &dyn Clock) -> i64 {
+        (clock.timestamp() - self.started_at).max(0)
+    }
+
+    // The record for the session if it ended now
+    pub fn to_record(&self, clock: &dyn Clock) -> Record {
+        let today = clock.today();
+        Record {
+            id: new_id(),
+            note: self.note.clone(),
+            date: self.date.clone(),
+            start_time: Some(self.start.clone()),
+            end_time: Some(clock.now()),
+            end_date: Some(today).filter(|today| *today != self.date),
+            amount: None,
+            pomodoro: false,
+        }
+    }
+}
+
+impl UserData {
+    /* The edit that stops the timer and adds its record. Returns None if no
+     * timer is running. If the habit was removed the timer is only
+     * stopped.
+     */
+    pub fn stop_timer(&self, clock: &dyn Clock) -> Option<(Edit, Option<Record>)> {
+        let timer = self.timer.as_ref()?;
+        let Some(habit) = self.find_habit(timer.habit_id) else {
+            return Some((Edit::SetTimer(None), None));
+        };
+        let record = timer.to_record(clock);
+        let edit = Edit::Batch(vec![
+            Edit::SetTimer(None),
+            Edit::InsertRecord {
+                habit_id: habit.id,
+                index: habit.records.len(),
+                record: record.clone(),
+            },
+        ]);
+        Some((edit, Some(record)))
+    }
+}
+
+// Like 1:05:09, or 5:09 under an hour
+pub fn format_seconds(seconds: i64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}